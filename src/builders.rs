@@ -1,26 +1,100 @@
-use std::mem;
+use std::{mem, path::PathBuf, time::Duration};
 
 use typed_builder::TypedBuilder;
-use crate::types::{Family, SocketType, Flag};
+use crate::connect::CONNECTION_ATTEMPT_DELAY;
+use crate::types::{Family, SocketType, Flags, ResolverKind, ServerMode};
 
 #[derive(PartialEq, TypedBuilder)]
 pub struct AddrInfo {
     #[builder(default=Family::Unspecified)]
     family: Family,
-    #[builder(default=SocketType::Stream)]
-    socktype: SocketType,
-    #[builder(default=Flag::None)]
-    flags: Flag
+    /// `None` returns every socket type
+    #[builder(default=Some(SocketType::Stream), setter(into))]
+    socktype: Option<SocketType>,
+    #[builder(default=Flags::empty(), setter(into))]
+    flags: Flags
 }
 
-impl Into<libc::addrinfo> for AddrInfo {
-    fn into(self) -> libc::addrinfo {
+impl AddrInfo {
+    pub fn family(&self) -> Family {
+        self.family.clone()
+    }
+
+    pub fn socktype(&self) -> Option<SocketType> {
+        self.socktype
+    }
+
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+}
+
+impl From<AddrInfo> for libc::addrinfo {
+    fn from(hints: AddrInfo) -> Self {
         unsafe {
             let mut addrinfo: libc::addrinfo = mem::zeroed();
-            addrinfo.ai_family = self.family.into();
-            addrinfo.ai_socktype = self.socktype.into();
-            addrinfo.ai_flags = self.flags.into();
+            addrinfo.ai_family = hints.family.into();
+            addrinfo.ai_socktype = hints.socktype.map_or(0, Into::into);
+            addrinfo.ai_flags = hints.flags.into();
             addrinfo
         }
     }
-}
\ No newline at end of file
+}
+
+/// Settings of [`crate::examples::streamserver`], the defaults are the ones of the book
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct StreamServer {
+    #[builder(default=String::from("localhost"), setter(into))]
+    pub host: String,
+    #[builder(default=3490)]
+    pub port: u16,
+    #[builder(default=Family::Unspecified)]
+    pub family: Family,
+    /// How many pending connections the queue will hold
+    #[builder(default=10)]
+    pub backlog: libc::c_int,
+    /// Sent to every client before closing the connection
+    #[builder(default=b"Hello, world!".to_vec(), setter(into))]
+    pub banner: Vec<u8>,
+    #[builder(default=ServerMode::Iterative)]
+    pub mode: ServerMode,
+    /// Number of threads with [`ServerMode::Pool`]
+    #[builder(default=4)]
+    pub workers: usize,
+}
+
+/// Settings of [`crate::examples::streamclient`], the defaults are the ones of the book
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct StreamClient {
+    #[builder(setter(into))]
+    pub host: String,
+    #[builder(default=3490)]
+    pub port: u16,
+    #[builder(default=Family::Unspecified)]
+    pub family: Family,
+    /// Most bytes we can get at once
+    #[builder(default=100)]
+    pub max_data_size: usize,
+    /// Where the received bytes go, stdout when `None`
+    #[builder(default, setter(into))]
+    pub output: Option<PathBuf>,
+    /// Stop after this many bytes, instead of reading until EOF
+    #[builder(default, setter(into))]
+    pub max_bytes: Option<u64>,
+    /// Give up when nothing arrives for this long
+    #[builder(default, setter(into))]
+    pub timeout: Option<Duration>,
+    /// Give up on an address that doesn't answer in this long, see [`crate::connect::connect_timeout`]
+    #[builder(default, setter(into))]
+    pub connect_timeout: Option<Duration>,
+    /// Race the addresses with [`crate::connect::happy_eyeballs`]
+    #[builder(default=false)]
+    pub happy_eyeballs: bool,
+    #[builder(default=CONNECTION_ATTEMPT_DELAY)]
+    pub attempt_delay: Duration,
+    #[builder(default=ResolverKind::System)]
+    pub resolver: ResolverKind,
+    /// Hosts file used by [`ResolverKind::Builtin`], see [`crate::dns::ResolverChain::from_system`]
+    #[builder(default, setter(into))]
+    pub hosts_file: Option<PathBuf>,
+}
@@ -0,0 +1,184 @@
+//! Section 7.5 "Serialization—How to Pack Data", the byte order and float helpers
+//!
+//! [`pack754`] and [`unpack754`] follow the book's `ieee754.c`: the float is normalized by
+//! halving or doubling it, so the encoding doesn't depend on how the host stores floats.
+//! Unlike the book, we also encode the special values: signed zeros, subnormals,
+//! infinities and NaN, and we round to nearest, ties to even, like the hardware does.
+
+use std::{error, fmt};
+
+/// `htons` for 64-bit values, which the C library doesn't have
+pub fn htonll(x: u64) -> u64 {
+    x.to_be()
+}
+
+/// `ntohs` for 64-bit values
+pub fn ntohll(x: u64) -> u64 {
+    u64::from_be(x)
+}
+
+/// `x * 2^exp` without overflowing or losing bits on the way, when the result is representable
+fn ldexp(x: f64, exp: i32) -> f64 {
+    let pow2 = |exp: i32| f64::from_bits(((exp + 1023) as u64) << 52);
+    match exp {
+        1024.. => x * pow2(1023) * pow2(exp - 1023),
+        ..=-1023 => x * pow2(exp + 1022) * pow2(-1022),
+        _ => x * pow2(exp),
+    }
+}
+
+fn check_format(bits: u32, expbits: u32) {
+    assert!(bits <= 64, "at most 64 bits");
+    // wider exponents would describe numbers an f64 can't hold
+    assert!((2..=11).contains(&expbits), "2 to 11 exponent bits");
+    assert!(bits > expbits + 1, "no room left for the significand");
+    assert!(bits - expbits - 1 <= 52, "at most 52 significand bits");
+}
+
+/// Encode `f` as an IEEE 754 float of `bits` bits, `expbits` of them for the exponent.
+///
+/// `pack754(f, 32, 8)` is a `float` and `pack754(f, 64, 11)` a `double`. Values too large
+/// for the format become infinities, too small ones become zeros or subnormals.
+/// Every NaN becomes the quiet NaN without payload.
+///
+/// # Panics
+///
+/// If the format has more than 11 exponent bits or 52 significand bits.
+pub fn pack754(f: f64, bits: u32, expbits: u32) -> u64 {
+    check_format(bits, expbits);
+    let significandbits = bits - expbits - 1; // -1 for sign bit
+    let max_exp = (1u64 << expbits) - 1;
+    let bias = (1i32 << (expbits - 1)) - 1;
+    let sign = u64::from(f.is_sign_negative()) << (bits - 1);
+
+    if f.is_nan() {
+        // quiet NaN: the top bit of the significand is set
+        return (max_exp << significandbits) | (1 << (significandbits - 1));
+    }
+    if f.is_infinite() {
+        return sign | (max_exp << significandbits);
+    }
+    // get this special case out of the way, keeping the sign of -0.0
+    if f == 0.0 {
+        return sign;
+    }
+
+    // get the normalized form of f and track the exponent
+    let mut fnorm = f.abs();
+    let mut shift = 0;
+    while fnorm >= 2.0 {
+        fnorm /= 2.0;
+        shift += 1;
+    }
+    while fnorm < 1.0 {
+        fnorm *= 2.0;
+        shift -= 1;
+    }
+
+    // get the biased exponent
+    let exp = shift + bias;
+    if exp >= max_exp as i32 {
+        return sign | (max_exp << significandbits);
+    }
+    let (exp, significand) = if exp > 0 {
+        // drop the implicit leading 1, multiplying by a power of 2 is exact
+        let significand = ldexp(fnorm - 1.0, significandbits as i32);
+        (exp as u64, significand.round_ties_even() as u64)
+    } else {
+        // subnormal: no implicit 1, and the exponent stays at its minimum
+        let significand = ldexp(fnorm, exp - 1 + significandbits as i32);
+        (0, significand.round_ties_even() as u64)
+    };
+    // rounding up might carry into the exponent, which is what IEEE 754 wants:
+    // the largest subnormal becomes the smallest normal, the largest normal infinity
+    sign | ((exp << significandbits) + significand)
+}
+
+/// Decode `i`, an IEEE 754 float of `bits` bits with `expbits` for the exponent
+///
+/// # Panics
+///
+/// Same as [`pack754`].
+pub fn unpack754(i: u64, bits: u32, expbits: u32) -> f64 {
+    check_format(bits, expbits);
+    let significandbits = bits - expbits - 1; // -1 for sign bit
+    let max_exp = (1u64 << expbits) - 1;
+    let bias = (1i32 << (expbits - 1)) - 1;
+    let sign = if (i >> (bits - 1)) & 1 == 1 {
+        -1.0
+    } else {
+        1.0
+    };
+
+    let significand = i & ((1u64 << significandbits) - 1); // mask
+    let exp = (i >> significandbits) & max_exp;
+
+    let result = match exp {
+        0 => ldexp(significand as f64, 1 - bias - significandbits as i32),
+        _ if exp == max_exp && significand == 0 => f64::INFINITY,
+        _ if exp == max_exp => f64::NAN,
+        _ => {
+            // add the one back on
+            let significand = significand | (1 << significandbits);
+            ldexp(
+                significand as f64,
+                exp as i32 - bias - significandbits as i32,
+            )
+        }
+    };
+    // copysign keeps the sign of zeros, multiplying would too but not for NaN
+    result.copysign(sign)
+}
+
+/// `pack754` for a C `float`
+pub fn pack754_32(f: f32) -> u32 {
+    pack754(f.into(), 32, 8) as u32
+}
+
+/// `unpack754` for a C `float`
+pub fn unpack754_32(i: u32) -> f32 {
+    unpack754(i.into(), 32, 8) as f32
+}
+
+/// `pack754` for a C `double`
+pub fn pack754_64(f: f64) -> u64 {
+    pack754(f, 64, 11)
+}
+
+/// `unpack754` for a C `double`
+pub fn unpack754_64(i: u64) -> f64 {
+    unpack754(i, 64, 11)
+}
+
+/// [`pack754`] disagreeing with the bytes of the native float
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    pub portable: u64,
+    pub native: u64,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pack754 gives {:#018x}, to_be_bytes gives {:#018x}",
+            self.portable, self.native
+        )
+    }
+}
+
+impl error::Error for Mismatch {}
+
+/// [`pack754_64`], checked against [`f64::to_be_bytes`].
+///
+/// NaNs only have to be NaNs on both sides: their payload is not portable.
+pub fn pack754_checked(f: f64) -> Result<u64, Mismatch> {
+    let portable = pack754_64(f);
+    let native = u64::from_be_bytes(f.to_be_bytes());
+    let nan = |bits: u64| f64::from_bits(bits).is_nan();
+    if portable == native || (nan(portable) && nan(native)) {
+        Ok(portable)
+    } else {
+        Err(Mismatch { portable, native })
+    }
+}
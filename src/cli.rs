@@ -1,8 +1,20 @@
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::{
+    ffi::OsString,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+    process::ExitCode,
+};
 
 use clap::{Parser, Subcommand};
 
-use crate::types::Family;
+use crate::{
+    connect::CONNECTION_ATTEMPT_DELAY,
+    discovery::MDNS_PORT,
+    framing::MAX_FRAME_SIZE,
+    resolver::ResolveError,
+    shutdown::GOODBYE,
+    types::{Backend, Family, Flag, Framing, NameInfoFlag, OutputFormat, ResolverKind, ServerMode, SocketType},
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -28,17 +40,129 @@ pub enum Commands {
         #[arg(short, long, default_value = "http")]
         service: String,
 
+        /// Socket type to ask getaddrinfo for, every type it knows when omitted
+        #[arg(long, value_enum)]
+        socktype: Option<SocketType>,
+
+        /// Hints for getaddrinfo (AI_*), can be repeated
+        #[arg(long = "flag", value_enum)]
+        flags: Vec<Flag>,
+
+        /// Output format, `json` and `csv` have one record per result
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Resolve with glibc (`system`) or with our own DNS client (`builtin`)
+        #[arg(long, value_enum, default_value_t = ResolverKind::System)]
+        resolver: ResolverKind,
+
+        /// Hosts file used by the builtin resolver, then DNS. By default the order of
+        /// /etc/nsswitch.conf is followed, with /etc/hosts
+        #[arg(long)]
+        hosts_file: Option<PathBuf>,
+    },
+
+    /// Section 9.8 "getnameinfo()":
+    /// Show host and service names for an IP address
+    Reverse {
+        /// IPv4 or IPv6 address to look up
+        ip: IpAddr,
+
+        /// Port to look up the service name for
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Flags for getnameinfo (NI_*), can be repeated
+        #[arg(long = "flag", value_enum)]
+        flags: Vec<NameInfoFlag>,
     },
 
     /// Section 6.1 "A Simple Stream Server":
     /// TCP server
-    StreamServer,
+    StreamServer {
+        /// Host to bind to
+        #[arg(long, default_value = "localhost")]
+        host: String,
+
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 3490)]
+        port: u16,
+
+        /// Ipv4 or Ipv6
+        #[arg(short, long, value_enum, default_value_t = Family::Unspecified)]
+        family: Family,
+
+        /// How many pending connections the queue will hold
+        #[arg(long, default_value_t = 10)]
+        backlog: i32,
+
+        /// Message sent to every client
+        #[arg(long, default_value = "Hello, world!")]
+        banner: String,
+
+        /// Send the contents of this file instead of --banner
+        #[arg(long, conflicts_with = "banner")]
+        banner_file: Option<PathBuf>,
+
+        /// How connections are served
+        #[arg(long, value_enum, default_value_t = ServerMode::Iterative)]
+        mode: ServerMode,
+
+        /// Number of threads with `--mode pool`
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+        workers: u16,
+    },
 
     /// Section 6.2 "A Simple Stream Client":
     /// TCP client
     StreamClient {
         /// URL to connect to
         host: String,
+
+        /// Port to connect to
+        #[arg(short, long, default_value_t = 3490)]
+        port: u16,
+
+        /// Ipv4 or Ipv6
+        #[arg(short, long, value_enum, default_value_t = Family::Unspecified)]
+        family: Family,
+
+        /// Most bytes we can get at once
+        #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+        max_data_size: u32,
+
+        /// Write what we receive to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Stop after receiving this many bytes, instead of reading until EOF
+        #[arg(long)]
+        max_bytes: Option<u64>,
+
+        /// Milliseconds to wait for data before giving up
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: Option<u64>,
+
+        /// Milliseconds to wait for each address to accept the connection
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        connect_timeout: Option<u64>,
+
+        /// Race the addresses (RFC 8305) instead of trying them one by one
+        #[arg(long, conflicts_with = "connect_timeout")]
+        happy_eyeballs: bool,
+
+        /// Milliseconds between connection attempts with --happy-eyeballs
+        #[arg(long, default_value_t = CONNECTION_ATTEMPT_DELAY.as_millis() as u64)]
+        attempt_delay: u64,
+
+        /// Resolve with glibc (`system`) or with our own DNS client (`builtin`)
+        #[arg(long, value_enum, default_value_t = ResolverKind::System)]
+        resolver: ResolverKind,
+
+        /// Hosts file used by the builtin resolver, then DNS. By default the order of
+        /// /etc/nsswitch.conf is followed, with /etc/hosts
+        #[arg(long)]
+        hosts_file: Option<PathBuf>,
     },
 
     /// Section 6.3 "Datagram Sockets":
@@ -51,6 +175,10 @@ pub enum Commands {
 
         #[arg(short, long, default_value_t = Family::Ipv6)]
         family: Family,
+
+        /// Decode the datagrams with this pack format, like "hsd"
+        #[arg(long)]
+        unpack: Option<String>,
     },
 
     /// Section 6.3 "Datagram Sockets":
@@ -65,32 +193,94 @@ pub enum Commands {
         #[arg(short, long, default_value_t = 4950)]
         port: u16,
 
+        /// Pack the message with this format, like "hsd", one word per field
+        #[arg(long)]
+        pack: Option<String>,
+
         /// Message to send
-        message: String,
+        #[arg(required = true)]
+        message: Vec<String>,
     },
 
     /// Section 7.2 "poll() - Synchonous I/O Multiplexing":
     /// Poll stdin for input
     PollStdIn,
 
-    /// Section 7.2 "poll() - Synchonous I/O Multiplexing":
-    /// Poll server for input
-    PollServer {
+    /// Sections 7.2 and 7.3:
+    /// Multi-user chat server, waiting with poll, select or epoll
+    #[command(alias = "poll-server")]
+    ChatServer {
         /// Port used by localhost
         #[arg(short, long, default_value_t = 9034)]
         port: u16,
+
+        /// Sent to the connected clients when we stop
+        #[arg(long, default_value = GOODBYE)]
+        goodbye: String,
+
+        /// How messages are cut, so we relay them whole
+        #[arg(long, value_enum, default_value_t = Framing::Raw)]
+        framing: Framing,
+
+        /// Clients sending bigger messages are disconnected
+        #[arg(long, default_value_t = MAX_FRAME_SIZE)]
+        max_frame_size: usize,
+
+        /// System call to wait for the clients with
+        #[arg(long, value_enum, default_value_t = Backend::Poll, overrides_with = "backend")]
+        backend: Backend,
+
+        /// Answer the discover queries for _beej-chat._tcp.local under this name
+        #[arg(long)]
+        announce: Option<String>,
+
+        /// Port of the discover queries, the mDNS one by default
+        #[arg(long, default_value_t = MDNS_PORT)]
+        discovery_port: u16,
+
+        /// Interface to answer the discover queries on, like eth0
+        #[arg(long)]
+        discovery_interface: Option<String>,
+    },
+
+    /// Sections 7.2 and 7.3:
+    /// Chat client for chat-server
+    ChatClient {
+        /// Host of the chat server
+        #[arg(default_value = "localhost")]
+        host: String,
+
+        /// Port of the chat server
+        #[arg(short, long, default_value_t = 9034)]
+        port: u16,
+
+        /// Ipv4 or Ipv6
+        #[arg(short, long, value_enum, default_value_t = Family::Unspecified)]
+        family: Family,
     },
 
     /// Section 7.3 "select()—Synchronous I/O Multiplexing, Old School":
     /// Wait for something to appear on standard input
     Select,
 
-    /// Section 7.3 "select()—Synchronous I/O Multiplexing, Old School":
-    /// Simple multi-user chat server
-    SelectServer {
-        /// Port used by localhost
-        #[arg(short, long, default_value_t = 9034)]
-        port: u16,
+    /// Section 7.5 "Serialization—How to Pack Data":
+    /// Portable encoding of floats
+    Ieee754 {
+        /// Floats to encode, like 3.14, -0, inf or nan
+        #[arg(required = true, allow_hyphen_values = true)]
+        values: Vec<f64>,
+
+        /// Size of the encoded float
+        #[arg(long, default_value_t = 64, value_parser = clap::value_parser!(u32).range(4..=64))]
+        bits: u32,
+
+        /// Bits of the exponent
+        #[arg(long, default_value_t = 11, value_parser = clap::value_parser!(u32).range(2..=11))]
+        expbits: u32,
+
+        /// Compare the encoding with the native one of f64
+        #[arg(long)]
+        check: bool,
     },
 
     /// Section 7.7 "Broadcast Packets—Hello, World!":
@@ -105,7 +295,150 @@ pub enum Commands {
         #[arg(short, long, default_value_t = 4950)]
         port: u16,
 
+        /// Send to the broadcast address of the subnet of this interface, like eth0
+        #[arg(long, conflicts_with = "host")]
+        interface: Option<String>,
+
+        /// Send to the broadcast address of every interface that supports broadcast
+        #[arg(long, conflicts_with_all = ["host", "interface"])]
+        all_interfaces: bool,
+
+        /// Message to send
+        message: String,
+    },
+
+    /// Section 7.7 "Broadcast Packets—Hello, World!":
+    /// UDP server receiving the broadcasts
+    BroadcastListener {
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 4950)]
+        port: u16,
+    },
+
+    /// Beyond section 7.7:
+    /// Find the chat servers started with --announce
+    Discover {
+        /// Family of the mDNS group to ask
+        #[arg(short, long, value_enum, default_value_t = Family::Ipv4)]
+        family: Family,
+
+        /// Port of the servers' discovery, the mDNS one by default
+        #[arg(short, long, default_value_t = MDNS_PORT)]
+        port: u16,
+
+        /// Interface to ask on, like eth0
+        #[arg(long)]
+        interface: Option<String>,
+
+        /// Broadcast the query instead of sending it to the mDNS group, IPv4 only
+        #[arg(long)]
+        broadcast: bool,
+
+        /// Milliseconds to wait for the answers
+        #[arg(long, default_value_t = 1000)]
+        timeout: u64,
+    },
+
+    /// Beyond section 7.7:
+    /// Send a UDP datagram to a multicast group
+    MulticastSend {
+        /// IPv4 or IPv6 multicast group
+        group: IpAddr,
+
         /// Message to send
         message: String,
+
+        /// Port of the receivers
+        #[arg(short, long, default_value_t = 4951)]
+        port: u16,
+
+        /// TTL, or hop limit for IPv6: 1 stays on the local network
+        #[arg(long, default_value_t = 1)]
+        hops: u8,
+
+        /// Don't deliver a copy to the receivers on this host
+        #[arg(long)]
+        no_loop: bool,
+
+        /// Interface to send on, like eth0
+        #[arg(long)]
+        interface: Option<String>,
+    },
+
+    /// Beyond section 7.7:
+    /// Join a multicast group and print what is sent to it
+    MulticastRecv {
+        /// IPv4 or IPv6 multicast group
+        group: IpAddr,
+
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 4951)]
+        port: u16,
+
+        /// Interface to join the group on, like eth0
+        #[arg(long)]
+        interface: Option<String>,
+
+        /// Only receive what this address sends to the group (source-specific multicast)
+        #[arg(long)]
+        source: Option<IpAddr>,
+    },
+}
+
+
+impl Commands {
+    /// What the errors returned by the command are prefixed with, like its other messages
+    pub fn error_prefix(&self) -> &'static str {
+        match self {
+            Commands::ShowIp { .. } => "show-ip",
+            Commands::Reverse { .. } => "reverse",
+            Commands::StreamServer { .. } => "server",
+            Commands::StreamClient { .. } => "client",
+            Commands::ChatClient { .. } => "chat-client",
+            // the others report their errors themselves
+            _ => "beej-rs",
+        }
+    }
+}
+
+/// `select-server` was a subcommand of its own, it's now `chat-server --backend select`.
+///
+/// A `--backend` given after it still wins.
+pub fn legacy_args(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut args: Vec<OsString> = args.into_iter().collect();
+    if args.get(1).is_some_and(|command| command == "select-server") {
+        args.splice(1..2, ["chat-server".into(), "--backend=select".into()]);
     }
+    args
+}
+
+/// Exit status for a failed name resolution, so scripts can tell failures apart.
+///
+/// Codes follow `sysexits.h`:
+///
+/// - `68` (`EX_NOHOST`): the host or service doesn't exist
+/// - `75` (`EX_TEMPFAIL`): temporary failure, try again later
+/// - `69` (`EX_UNAVAILABLE`): the name server failed permanently
+/// - `64` (`EX_USAGE`): invalid hints or arguments
+/// - `71` (`EX_OSERR`): out of memory or system error
+/// - `70` (`EX_SOFTWARE`): anything else
+pub fn exit_code(error: &ResolveError) -> ExitCode {
+    let code = match error {
+        ResolveError::NoName | ResolveError::NoData | ResolveError::AddrFamily => 68,
+        ResolveError::Again | ResolveError::Interrupted => 75,
+        ResolveError::Fail => 69,
+        ResolveError::BadFlags
+        | ResolveError::Family
+        | ResolveError::SockType
+        | ResolveError::Service
+        | ResolveError::IdnEncode
+        | ResolveError::InvalidName(_) => 64,
+        ResolveError::Memory | ResolveError::Overflow | ResolveError::System(_) => 71,
+        ResolveError::InProgress
+        | ResolveError::Canceled
+        | ResolveError::NotCanceled
+        | ResolveError::AllDone
+        | ResolveError::Unknown(_) => 70,
+    };
+    ExitCode::from(code)
 }
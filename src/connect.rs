@@ -0,0 +1,189 @@
+use std::{
+    fmt, io,
+    net::SocketAddr,
+    os::fd::AsFd,
+    time::{Duration, Instant},
+};
+
+use nix::{
+    errno::Errno,
+    poll::{PollFd, PollFlags, PollTimeout},
+};
+
+use crate::socket::Socket;
+
+/// Every address [`connect_timeout`] or [`happy_eyeballs`] tried, with the reason it failed
+#[derive(Debug)]
+pub struct ConnectError {
+    pub attempts: Vec<(SocketAddr, io::Error)>,
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.attempts.is_empty() {
+            return write!(f, "no addresses to connect to");
+        }
+        for (i, (addr, e)) in self.attempts.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", addr, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+/// Recommended "Connection Attempt Delay" from RFC 8305
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Reorder the addresses so families alternate, keeping the order within each family.
+///
+/// The family of the first address goes first, like the RFC asks.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let preferred_v6 = first.is_ipv6();
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == preferred_v6);
+
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+    let mut interleaved = Vec::new();
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+    interleaved
+}
+
+/// Start a non-blocking connect, the flag tells if the connection is already established
+fn start_connect(addr: SocketAddr) -> io::Result<(socket2::Socket, bool)> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(addr),
+        socket2::Type::STREAM,
+        None,
+    )?;
+    socket.set_nonblocking(true)?;
+    match socket.connect(&addr.into()) {
+        Ok(()) => Ok((socket, true)),
+        Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => Ok((socket, false)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Connect to the first address that answers, racing them like RFC 8305 "Happy Eyeballs v2".
+///
+/// Families are interleaved, and a new attempt starts every `delay` (or as soon as
+/// the previous one fails) while the others are still in flight.
+/// The first socket to complete wins, the rest are closed.
+///
+/// The returned socket is back in blocking mode.
+/// When none works, the error holds every address with its `errno`, like [`connect_timeout`].
+pub fn happy_eyeballs<I>(addrs: I, delay: Duration) -> Result<(Socket, SocketAddr), ConnectError>
+where
+    I: IntoIterator<Item = SocketAddr>,
+{
+    let mut pending = interleave(addrs.into_iter().collect()).into_iter();
+    let mut in_flight: Vec<(socket2::Socket, SocketAddr)> = Vec::new();
+    let mut attempts = Vec::new();
+    let mut next_attempt = Instant::now();
+
+    loop {
+        // Start a new attempt if it's time, or if nothing else is going on
+        if Instant::now() >= next_attempt || in_flight.is_empty() {
+            if let Some(addr) = pending.next() {
+                match start_connect(addr) {
+                    Ok((socket, true)) => match socket.set_nonblocking(false) {
+                        Ok(()) => return Ok((socket.into(), addr)),
+                        Err(e) => attempts.push((addr, e)),
+                    },
+                    Ok((socket, false)) => {
+                        in_flight.push((socket, addr));
+                        next_attempt = Instant::now() + delay;
+                    }
+                    Err(e) => attempts.push((addr, e)),
+                }
+                continue;
+            } else if in_flight.is_empty() {
+                return Err(ConnectError { attempts });
+            }
+        }
+
+        // Wait until one of the attempts is done, or until it's time for the next one
+        let timeout = if pending.len() > 0 {
+            let left = next_attempt.saturating_duration_since(Instant::now());
+            PollTimeout::try_from(left).unwrap_or(PollTimeout::MAX)
+        } else {
+            PollTimeout::NONE
+        };
+        let mut pfds: Vec<PollFd> = in_flight
+            .iter()
+            .map(|(socket, _)| PollFd::new(socket.as_fd(), PollFlags::POLLOUT))
+            .collect();
+        match nix::poll::poll(&mut pfds, timeout) {
+            Ok(_) | Err(Errno::EINTR) => {}
+            // we can't tell how the attempts in flight went, they all failed with it
+            Err(e) => {
+                drop(pfds);
+                attempts.extend(in_flight.into_iter().map(|(_, addr)| (addr, e.into())));
+                return Err(ConnectError { attempts });
+            }
+        }
+
+        let done: Vec<usize> = pfds
+            .iter()
+            .enumerate()
+            .filter(|(_, pfd)| pfd.revents().is_some_and(|e| !e.is_empty()))
+            .map(|(i, _)| i)
+            .collect();
+        drop(pfds);
+
+        // Remove from the back so the indexes stay valid
+        for i in done.into_iter().rev() {
+            let (socket, addr) = in_flight.remove(i);
+            let connected = socket.take_error().and_then(|error| match error {
+                None => socket.set_nonblocking(false),
+                Some(e) => Err(e),
+            });
+            match connected {
+                Ok(()) => return Ok((socket.into(), addr)),
+                Err(e) => {
+                    attempts.push((addr, e));
+                    // A failure means we can start the next attempt right away
+                    next_attempt = Instant::now();
+                }
+            }
+        }
+    }
+}
+
+/// Connect to the first address that answers, trying them one after the other
+/// and giving each one `timeout` with [`Socket::connect_timeout`].
+///
+/// When none works, the error holds every address with its `errno`:
+/// `ETIMEDOUT` for the ones that didn't answer in time.
+pub fn connect_timeout<I>(addrs: I, timeout: Duration) -> Result<(Socket, SocketAddr), ConnectError>
+where
+    I: IntoIterator<Item = SocketAddr>,
+{
+    let mut attempts = Vec::new();
+    for addr in addrs {
+        let sockaddr = socket2::SockAddr::from(addr);
+        let attempt = Socket::from_args(sockaddr.family() as libc::c_int, libc::SOCK_STREAM, 0)
+            .and_then(|socket| {
+                socket.connect_timeout(&sockaddr, timeout)?;
+                Ok(socket)
+            });
+        match attempt {
+            Ok(socket) => return Ok((socket, addr)),
+            Err(e) => attempts.push((addr, e)),
+        }
+    }
+    Err(ConnectError { attempts })
+}
@@ -0,0 +1,375 @@
+//! Finding the chat servers of the LAN, on top of multicast and broadcast
+//!
+//! The datagrams are DNS messages, like in multicast DNS (RFC 6762) and DNS-based service
+//! discovery (RFC 6763), so we reuse [`crate::dns`] for the wire format:
+//!
+//! - a query asks for the `PTR` records of [`SERVICE`]
+//! - each server announces itself with a `PTR` to its instance, `<name>._beej-chat._tcp.local`,
+//!   a `SRV` record with its port, and a `TXT` record with `family=ipv4`, `family=ipv6`,
+//!   or `family=unspecified` when it takes both
+//!
+//! We only answer queries, there is no probing for name conflicts nor unsolicited announcements.
+//! The address to connect to is where the answer came from.
+//!
+//! Queries not sent from the mDNS port get a unicast answer, as RFC 6762 section 6.7 wants,
+//! so `dig -p 5353 @224.0.0.251 _beej-chat._tcp.local PTR` or `avahi-browse _beej-chat._tcp`
+//! can find our servers too.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, UdpSocket},
+    os::fd::AsRawFd,
+    thread::{self, JoinHandle},
+};
+
+use clap::ValueEnum;
+use nix::sys::socket::{self, sockopt, AddressFamily, SockFlag, SockType, SockaddrStorage};
+
+use crate::{
+    dns::{Header, Message, Question, Rcode, Record, RecordData, RecordType, CLASS_IN},
+    multicast,
+    types::Family,
+};
+
+/// The DNS-SD service type of the chat servers
+pub const SERVICE: &str = "_beej-chat._tcp.local";
+
+/// The mDNS port
+pub const MDNS_PORT: u16 = 5353;
+
+/// The IPv4 mDNS group
+pub const MDNS_IPV4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+/// The IPv6 mDNS group, link-local
+pub const MDNS_IPV6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+
+/// Seconds the answers may be cached, RFC 6762 recommends 120 for `SRV` records
+pub const TTL: u32 = 120;
+
+/// RFC 6762 section 10.2: the top bit of the class, the record replaces the cached ones
+const CACHE_FLUSH: u16 = 0x8000;
+
+/// RFC 6762 section 18.12: the top bit of the class of a question, unicast answer wanted
+const UNICAST_RESPONSE: u16 = 0x8000;
+
+/// `ANY`, the query type asking for every record
+const QTYPE_ANY: u16 = 255;
+
+/// A chat server, as it describes itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct Announcement {
+    /// Instance name, the first label of `<name>._beej-chat._tcp.local`
+    pub name: String,
+    pub port: u16,
+    /// Address family the server accepts, `Unspecified` when it doesn't say
+    pub family: Family,
+    /// Host name from the `SRV` record, like `lab1.local`
+    pub target: String,
+}
+
+impl Announcement {
+    /// Our chat server, on this host
+    pub fn new(name: String, port: u16, family: Family) -> Self {
+        let hostname = nix::unistd::gethostname()
+            .map(|h| h.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "localhost".to_string());
+        // a .local name has a single label before the domain
+        let host = hostname.split('.').next().unwrap_or("localhost");
+        Announcement {
+            name,
+            port,
+            family,
+            target: format!("{}.local", host),
+        }
+    }
+
+    /// `<name>._beej-chat._tcp.local`
+    pub fn instance(&self) -> String {
+        format!("{}.{}", self.name, SERVICE)
+    }
+
+    /// Our answer to `query`.
+    ///
+    /// A `legacy` querier, one not sending from the mDNS port, gets its id and question back,
+    /// and no cache-flush bits it wouldn't understand.
+    pub fn response(&self, query: &Message, legacy: bool) -> Message {
+        let instance = self.instance();
+        let class = if legacy {
+            CLASS_IN
+        } else {
+            CLASS_IN | CACHE_FLUSH
+        };
+        let record = |name: &str, rtype, class, data| Record {
+            name: name.to_string(),
+            rtype,
+            class,
+            ttl: TTL,
+            data,
+        };
+        // the question we answer, not whatever else the querier asked
+        let questions = if legacy {
+            query
+                .questions
+                .iter()
+                .filter(|q| asks_for_us(q))
+                .take(1)
+                .cloned()
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Message {
+            header: Header {
+                id: if legacy { query.header.id } else { 0 },
+                qr: true,
+                opcode: 0,
+                aa: true,
+                tc: false,
+                rd: false,
+                ra: false,
+                rcode: Rcode::NoError,
+            },
+            questions,
+            // several servers share the service name, so the PTR is never flushed
+            answers: vec![record(
+                SERVICE,
+                RecordType::Ptr,
+                CLASS_IN,
+                RecordData::Ptr(instance.clone()),
+            )],
+            authorities: Vec::new(),
+            additionals: vec![
+                record(
+                    &instance,
+                    RecordType::Srv,
+                    class,
+                    RecordData::Srv {
+                        priority: 0,
+                        weight: 0,
+                        port: self.port,
+                        target: self.target.clone(),
+                    },
+                ),
+                record(
+                    &instance,
+                    RecordType::Txt,
+                    class,
+                    RecordData::Txt(vec![format!("family={}", self.family).into_bytes()]),
+                ),
+            ],
+        }
+    }
+}
+
+/// DNS names are case-insensitive, and may end with the root dot
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+/// `lab1` for `lab1._beej-chat._tcp.local`
+fn instance_name(instance: &str) -> Option<&str> {
+    let instance = instance.trim_end_matches('.');
+    let split = instance.len().checked_sub(SERVICE.len() + 1)?;
+    let (name, service) = (instance.get(..split)?, instance.get(split..)?);
+    let service = service.strip_prefix('.')?;
+    (!name.is_empty() && same_name(service, SERVICE)).then_some(name)
+}
+
+/// The query for the chat servers, `id` is echoed back by the answers we get in unicast
+pub fn query(id: u16) -> Message {
+    let mut message = Message::query(id, SERVICE, RecordType::Ptr);
+    // no recursion in multicast DNS
+    message.header.rd = false;
+    message.questions[0].qclass = CLASS_IN | UNICAST_RESPONSE;
+    message
+}
+
+/// Whether `question` asks for the chat servers
+fn asks_for_us(question: &Question) -> bool {
+    same_name(&question.name, SERVICE)
+        && question.qclass & !UNICAST_RESPONSE == CLASS_IN
+        && (question.qtype == RecordType::Ptr || u16::from(question.qtype) == QTYPE_ANY)
+}
+
+/// Whether `message` asks for the chat servers
+pub fn is_query(message: &Message) -> bool {
+    !message.header.qr && message.header.opcode == 0 && message.questions.iter().any(asks_for_us)
+}
+
+/// The chat servers announced in `message`, an answer to [`query`].
+///
+/// The `SRV` and `TXT` records may come as answers or additional records,
+/// an instance without `SRV` record is skipped.
+pub fn announcements(message: &Message) -> Vec<Announcement> {
+    if !message.header.qr {
+        return Vec::new();
+    }
+    let records: Vec<&Record> = message.answers.iter().chain(&message.additionals).collect();
+    let mut found = Vec::new();
+    for record in &records {
+        let RecordData::Ptr(instance) = &record.data else {
+            continue;
+        };
+        if !same_name(&record.name, SERVICE) {
+            continue;
+        }
+        let Some(name) = instance_name(instance) else {
+            continue;
+        };
+        let srv = records.iter().find_map(|r| match &r.data {
+            RecordData::Srv { port, target, .. } if same_name(&r.name, instance) => {
+                Some((*port, target.clone()))
+            }
+            _ => None,
+        });
+        let Some((port, target)) = srv else {
+            continue;
+        };
+        let family = records
+            .iter()
+            .filter(|r| same_name(&r.name, instance))
+            .find_map(|r| match &r.data {
+                RecordData::Txt(strings) => strings
+                    .iter()
+                    .find_map(|s| s.strip_prefix(b"family="))
+                    .and_then(|value| std::str::from_utf8(value).ok())
+                    .and_then(|value| Family::from_str(value, true).ok()),
+                _ => None,
+            })
+            .unwrap_or(Family::Unspecified);
+        let announcement = Announcement {
+            name: name.to_string(),
+            port,
+            family,
+            target,
+        };
+        if !found.contains(&announcement) {
+            found.push(announcement);
+        }
+    }
+    found
+}
+
+/// Answers the queries for one [`Announcement`], on one multicast group
+#[derive(Debug)]
+pub struct Responder {
+    socket: UdpSocket,
+    announcement: Announcement,
+    group: IpAddr,
+    port: u16,
+}
+
+impl Responder {
+    /// Join `group` on `port`, sharing it with the other responders of the host.
+    ///
+    /// Broadcasts to the port are received too, when `group` is IPv4.
+    pub fn bind(
+        announcement: Announcement,
+        group: IpAddr,
+        port: u16,
+        interface: Option<&str>,
+    ) -> io::Result<Self> {
+        // better now than when the first query arrives
+        announcement
+            .response(&query(0), false)
+            .to_bytes()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+
+        let (family, unspecified) = match group {
+            IpAddr::V4(_) => (AddressFamily::Inet, IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpAddr::V6(_) => (AddressFamily::Inet6, IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        };
+        let sockfd = socket::socket(family, SockType::Datagram, SockFlag::empty(), None)?;
+        socket::setsockopt(&sockfd, sockopt::ReuseAddr, &true)?;
+        if family == AddressFamily::Inet6 {
+            // the IPv4 responder takes care of IPv4
+            socket::setsockopt(&sockfd, sockopt::Ipv6V6Only, &true)?;
+        }
+        let local_addr = SockaddrStorage::from(SocketAddr::new(unspecified, port));
+        socket::bind(sockfd.as_raw_fd(), &local_addr)?;
+        multicast::join(&sockfd, group, interface, None)?;
+        // 255 like every mDNS packet, receivers drop the others since they may come from afar
+        multicast::set_send_options(&sockfd, group, 255, true, interface)?;
+
+        Ok(Responder {
+            socket: UdpSocket::from(sockfd),
+            announcement,
+            group,
+            port,
+        })
+    }
+
+    pub fn announcement(&self) -> &Announcement {
+        &self.announcement
+    }
+
+    /// Answer queries until we can't receive any more
+    pub fn run(&self) -> io::Result<()> {
+        let mut buf = [0u8; 9000];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            // not everybody on the mDNS port talks to us
+            let Ok(message) = Message::parse(&buf[..len]) else {
+                continue;
+            };
+            if !is_query(&message) {
+                continue;
+            }
+            let legacy = from.port() != self.port;
+            // bind checked our records, but the question comes from the querier
+            let response = match self.announcement.response(&message, legacy).to_bytes() {
+                Ok(response) => response,
+                Err(e) => {
+                    eprintln!("discovery: can't answer {}: {}", from, e);
+                    continue;
+                }
+            };
+            let dest = match (legacy, self.group, from) {
+                (true, _, _) => from,
+                // the link-local group needs the interface the query came from
+                (false, IpAddr::V6(group), SocketAddr::V6(from)) => {
+                    SocketAddr::V6(SocketAddrV6::new(group, self.port, 0, from.scope_id()))
+                }
+                (false, group, _) => SocketAddr::new(group, self.port),
+            };
+            // one querier we can't reach is no reason to stop answering the others
+            if let Err(e) = self.socket.send_to(&response, dest) {
+                eprintln!("discovery: can't answer {}: {}", dest, e);
+            }
+        }
+    }
+
+    /// [`Responder::run`] in the background, for as long as the process lives
+    pub fn spawn(self) -> JoinHandle<()> {
+        thread::spawn(move || {
+            if let Err(e) = self.run() {
+                eprintln!("discovery: {}", e);
+            }
+        })
+    }
+}
+
+/// Start answering the queries for a chat server, on the IPv4 and IPv6 mDNS groups.
+///
+/// A group we can't join is reported and skipped, most hosts can still do the other one.
+pub fn announce(announcement: Announcement, port: u16, interface: Option<&str>) {
+    for group in [IpAddr::V4(MDNS_IPV4), IpAddr::V6(MDNS_IPV6)] {
+        match Responder::bind(announcement.clone(), group, port, interface) {
+            Ok(responder) => {
+                println!(
+                    "Announcing {} on {} port {}",
+                    announcement.instance(),
+                    group,
+                    port
+                );
+                responder.spawn();
+            }
+            Err(e) => eprintln!("discovery: can't answer on {}: {}", group, e),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::types::Family;
+
+use super::{DnsError, Hosts, IpLookup, Resolver, HOSTS_FILE, RESOLV_CONF};
+
+/// Where glibc reads the order of the name services from
+pub const NSSWITCH_CONF: &str = "/etc/nsswitch.conf";
+
+/// A place to look names up, like the services on the `hosts:` line of `nsswitch.conf(5)`
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// `files`: the hosts file
+    Hosts(Hosts),
+    /// `dns`: ask the nameservers, configured from a `resolv.conf` read by the first lookup
+    /// that gets this far, so names the hosts file knows don't depend on it
+    Dns {
+        resolv_conf: PathBuf,
+        resolver: OnceLock<Resolver>,
+    },
+}
+
+impl Source {
+    /// The `files` service, a missing hosts file is treated as an empty one
+    pub fn hosts_file(path: impl AsRef<Path>) -> Self {
+        Source::Hosts(Hosts::from_file(path).unwrap_or_default())
+    }
+
+    /// The `dns` service, configured from `resolv_conf` when it's needed
+    pub fn dns(resolv_conf: impl Into<PathBuf>) -> Self {
+        Source::Dns {
+            resolv_conf: resolv_conf.into(),
+            resolver: OnceLock::new(),
+        }
+    }
+
+    /// The `dns` service, with `resolver` already configured
+    pub fn resolver(resolver: Resolver) -> Self {
+        Source::Dns {
+            resolv_conf: PathBuf::from(RESOLV_CONF),
+            resolver: OnceLock::from(resolver),
+        }
+    }
+}
+
+/// Ordered list of sources, the first one that knows the name wins
+#[derive(Debug, Clone)]
+pub struct ResolverChain {
+    sources: Vec<Source>,
+}
+
+/// Service names from the `hosts:` line, `files dns` if there is none.
+///
+/// Action items like `[NOTFOUND=return]` are skipped, we always continue.
+pub fn parse_nsswitch_hosts(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .find_map(|line| line.trim().strip_prefix("hosts:"))
+        .map(|services| {
+            services
+                .split_whitespace()
+                .filter(|service| !service.starts_with('['))
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_else(|| vec!["files".to_string(), "dns".to_string()])
+}
+
+impl ResolverChain {
+    pub fn new(sources: Vec<Source>) -> Self {
+        ResolverChain { sources }
+    }
+
+    /// Follow the system configuration, see [`ResolverChain::from_files`].
+    ///
+    /// A `hosts_file` given by the user comes first and then `dns`, whatever
+    /// [`NSSWITCH_CONF`] says: it may not even ask for `files`.
+    pub fn from_system(hosts_file: Option<&Path>) -> Self {
+        match hosts_file {
+            Some(hosts_file) => ResolverChain::new(vec![
+                Source::hosts_file(hosts_file),
+                Source::dns(RESOLV_CONF),
+            ]),
+            None => Self::from_files(NSSWITCH_CONF, HOSTS_FILE, RESOLV_CONF),
+        }
+    }
+
+    /// Order the sources like the `hosts:` line of `nsswitch`.
+    ///
+    /// Only `files` and `dns` are supported, other services are ignored.
+    /// A missing `nsswitch` or hosts file is treated as an empty one.
+    /// `resolv_conf` is only read when a lookup reaches `dns`, and fails that lookup only.
+    pub fn from_files(
+        nsswitch: impl AsRef<Path>,
+        hosts_file: impl AsRef<Path>,
+        resolv_conf: impl AsRef<Path>,
+    ) -> Self {
+        let nsswitch = fs::read_to_string(nsswitch).unwrap_or_default();
+        let mut sources = Vec::new();
+        for service in parse_nsswitch_hosts(&nsswitch) {
+            match service.as_str() {
+                "files" => sources.push(Source::hosts_file(hosts_file.as_ref())),
+                "dns" => sources.push(Source::dns(resolv_conf.as_ref())),
+                _ => {}
+            }
+        }
+        ResolverChain::new(sources)
+    }
+
+    /// Ask every source in order, stopping at the first one that has an answer.
+    ///
+    /// The error of the last source is returned when nobody knows the name.
+    pub fn lookup_ip(&self, host: &str, family: Family) -> Result<IpLookup, DnsError> {
+        let mut last_error = DnsError::NxDomain(host.to_string());
+        for source in &self.sources {
+            match source {
+                Source::Hosts(hosts) => {
+                    if let Some(lookup) = hosts.lookup_ip(host, family.clone()) {
+                        return Ok(lookup);
+                    }
+                }
+                Source::Dns {
+                    resolv_conf,
+                    resolver,
+                } => {
+                    let resolver = match resolver.get() {
+                        Some(resolver) => resolver,
+                        None => match Resolver::from_file(resolv_conf) {
+                            Ok(new) => resolver.get_or_init(|| new),
+                            Err(e) => {
+                                last_error = e;
+                                continue;
+                            }
+                        },
+                    };
+                    match resolver.lookup_ip(host, family.clone()) {
+                        Ok(lookup) => return Ok(lookup),
+                        Err(e) => last_error = e,
+                    }
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs, io,
+    net::{IpAddr, SocketAddr, SocketAddrV6},
+    path::Path,
+};
+
+use crate::types::Family;
+
+use super::IpLookup;
+
+/// Where glibc reads static host names from
+pub const HOSTS_FILE: &str = "/etc/hosts";
+
+/// Parse an IP address with an optional IPv6 zone, like `fe80::1%eth0` or `fe80::1%2`.
+///
+/// The zone becomes the scope id of the returned address, the port is always 0.
+pub fn parse_scoped_ip(text: &str) -> Option<SocketAddr> {
+    let (ip, zone) = match text.split_once('%') {
+        Some((ip, zone)) => (ip, Some(zone)),
+        None => (text, None),
+    };
+    match (ip.parse::<IpAddr>().ok()?, zone) {
+        (ip, None) => Some(SocketAddr::new(ip, 0)),
+        (IpAddr::V6(ip), Some(zone)) => {
+            let scope_id = match zone.parse() {
+                Ok(index) => index,
+                Err(_) => nix::net::if_::if_nametoindex(zone).ok()?,
+            };
+            Some(SocketAddrV6::new(ip, 0, 0, scope_id).into())
+        }
+        // zones only make sense for IPv6
+        (IpAddr::V4(_), Some(_)) => None,
+    }
+}
+
+/// One line of the hosts file
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostsEntry {
+    /// Port is 0, the scope id comes from the zone if there was one
+    pub addr: SocketAddr,
+    /// The canonical name first, then the aliases
+    pub names: Vec<String>,
+}
+
+/// Static table of host names, in the format of `hosts(5)`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hosts {
+    pub entries: Vec<HostsEntry>,
+}
+
+impl Hosts {
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Parse the file contents, skipping lines we can't understand like glibc does
+    pub fn parse(contents: &str) -> Self {
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let line = line.split('#').next().unwrap_or_default();
+                let mut words = line.split_whitespace();
+                let addr = parse_scoped_ip(words.next()?)?;
+                let names: Vec<String> = words.map(String::from).collect();
+                (!names.is_empty()).then_some(HostsEntry { addr, names })
+            })
+            .collect();
+        Hosts { entries }
+    }
+
+    /// Every address for `host` in file order, IPv6 first when `family` allows both.
+    ///
+    /// A name can be on several lines, each one adds an address.
+    /// The canonical name is the first name of the first matching line.
+    pub fn lookup_ip(&self, host: &str, family: Family) -> Option<IpLookup> {
+        let matching: Vec<&HostsEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                entry
+                    .names
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(host))
+            })
+            .collect();
+        let canonname = matching.first()?.names[0].clone();
+
+        let v6 = matching.iter().filter(|entry| entry.addr.is_ipv6());
+        let v4 = matching.iter().filter(|entry| entry.addr.is_ipv4());
+        let addrs: Vec<SocketAddr> = match family {
+            Family::Ipv4 => v4.map(|entry| entry.addr).collect(),
+            Family::Ipv6 => v6.map(|entry| entry.addr).collect(),
+            Family::Unspecified => v6.chain(v4).map(|entry| entry.addr).collect(),
+        };
+
+        (!addrs.is_empty()).then_some(IpLookup { canonname, addrs })
+    }
+}
@@ -0,0 +1,465 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use super::DnsError;
+
+/// The only class anybody uses
+pub const CLASS_IN: u16 = 1;
+
+// Pointers can point to pointers, give up after this many jumps
+const MAX_POINTER_JUMPS: usize = 32;
+
+// RFC 1035 section 3.1: 255 bytes on the wire, 253 characters once written with dots
+const MAX_NAME_WIRE_LEN: usize = 255;
+
+/// Record types we know how to parse, see RFC 1035 section 3.2.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    A,
+    Cname,
+    Ptr,
+    Txt,
+    Aaaa,
+    Srv,
+    Other(u16),
+}
+
+impl From<RecordType> for u16 {
+    fn from(rtype: RecordType) -> Self {
+        match rtype {
+            RecordType::A => 1,
+            RecordType::Cname => 5,
+            RecordType::Ptr => 12,
+            RecordType::Txt => 16,
+            RecordType::Aaaa => 28,
+            RecordType::Srv => 33,
+            RecordType::Other(code) => code,
+        }
+    }
+}
+
+impl From<u16> for RecordType {
+    fn from(code: u16) -> Self {
+        match code {
+            1 => RecordType::A,
+            5 => RecordType::Cname,
+            12 => RecordType::Ptr,
+            16 => RecordType::Txt,
+            28 => RecordType::Aaaa,
+            33 => RecordType::Srv,
+            code => RecordType::Other(code),
+        }
+    }
+}
+
+/// Response codes from the header, see RFC 1035 section 4.1.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rcode {
+    NoError,
+    FormErr,
+    ServFail,
+    NxDomain,
+    NotImp,
+    Refused,
+    Other(u8),
+}
+
+impl From<u8> for Rcode {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => Rcode::NoError,
+            1 => Rcode::FormErr,
+            2 => Rcode::ServFail,
+            3 => Rcode::NxDomain,
+            4 => Rcode::NotImp,
+            5 => Rcode::Refused,
+            code => Rcode::Other(code),
+        }
+    }
+}
+
+impl From<Rcode> for u8 {
+    fn from(rcode: Rcode) -> Self {
+        match rcode {
+            Rcode::NoError => 0,
+            Rcode::FormErr => 1,
+            Rcode::ServFail => 2,
+            Rcode::NxDomain => 3,
+            Rcode::NotImp => 4,
+            Rcode::Refused => 5,
+            Rcode::Other(code) => code & 0x0f,
+        }
+    }
+}
+
+/// The fixed 12 bytes at the start of every message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub id: u16,
+    /// `true` for responses
+    pub qr: bool,
+    pub opcode: u8,
+    /// Authoritative answer
+    pub aa: bool,
+    /// Truncated, the answer didn't fit in a datagram: retry over TCP
+    pub tc: bool,
+    /// Recursion desired
+    pub rd: bool,
+    /// Recursion available
+    pub ra: bool,
+    pub rcode: Rcode,
+}
+
+impl Header {
+    fn flags(&self) -> u16 {
+        (self.qr as u16) << 15
+            | ((self.opcode & 0x0f) as u16) << 11
+            | (self.aa as u16) << 10
+            | (self.tc as u16) << 9
+            | (self.rd as u16) << 8
+            | (self.ra as u16) << 7
+            | u8::from(self.rcode) as u16
+    }
+
+    fn from_flags(id: u16, flags: u16) -> Self {
+        Header {
+            id,
+            qr: flags & (1 << 15) != 0,
+            opcode: ((flags >> 11) & 0x0f) as u8,
+            aa: flags & (1 << 10) != 0,
+            tc: flags & (1 << 9) != 0,
+            rd: flags & (1 << 8) != 0,
+            ra: flags & (1 << 7) != 0,
+            rcode: ((flags & 0x0f) as u8).into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Question {
+    pub name: String,
+    pub qtype: RecordType,
+    pub qclass: u16,
+}
+
+/// Parsed RDATA of a resource record
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(String),
+    Ptr(String),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    /// One or more character strings
+    Txt(Vec<Vec<u8>>),
+    /// Anything we don't parse, kept as raw bytes
+    Other(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub name: String,
+    pub rtype: RecordType,
+    pub class: u16,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+/// A DNS message, used both for queries and responses
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub header: Header,
+    pub questions: Vec<Question>,
+    pub answers: Vec<Record>,
+    pub authorities: Vec<Record>,
+    pub additionals: Vec<Record>,
+}
+
+impl Message {
+    /// A standard recursive query for a single name
+    pub fn query(id: u16, name: &str, qtype: RecordType) -> Self {
+        Message {
+            header: Header {
+                id,
+                qr: false,
+                opcode: 0,
+                aa: false,
+                tc: false,
+                rd: true,
+                ra: false,
+                rcode: Rcode::NoError,
+            },
+            questions: vec![Question {
+                name: name.to_string(),
+                qtype,
+                qclass: CLASS_IN,
+            }],
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    /// Encode in wire format. Names are never compressed.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+        let mut buf = Vec::with_capacity(512);
+        buf.extend_from_slice(&self.header.id.to_be_bytes());
+        buf.extend_from_slice(&self.header.flags().to_be_bytes());
+        for count in [
+            self.questions.len(),
+            self.answers.len(),
+            self.authorities.len(),
+            self.additionals.len(),
+        ] {
+            let count =
+                u16::try_from(count).map_err(|_| DnsError::Malformed("too many records"))?;
+            buf.extend_from_slice(&count.to_be_bytes());
+        }
+
+        for question in &self.questions {
+            write_name(&mut buf, &question.name)?;
+            buf.extend_from_slice(&u16::from(question.qtype).to_be_bytes());
+            buf.extend_from_slice(&question.qclass.to_be_bytes());
+        }
+        for record in self
+            .answers
+            .iter()
+            .chain(&self.authorities)
+            .chain(&self.additionals)
+        {
+            write_record(&mut buf, record)?;
+        }
+        Ok(buf)
+    }
+
+    /// Decode from wire format, following compression pointers
+    pub fn parse(bytes: &[u8]) -> Result<Self, DnsError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let id = reader.u16()?;
+        let flags = reader.u16()?;
+        let qdcount = reader.u16()?;
+        let ancount = reader.u16()?;
+        let nscount = reader.u16()?;
+        let arcount = reader.u16()?;
+
+        let mut questions = Vec::with_capacity(qdcount as usize);
+        for _ in 0..qdcount {
+            questions.push(Question {
+                name: reader.name()?,
+                qtype: reader.u16()?.into(),
+                qclass: reader.u16()?,
+            });
+        }
+
+        let answers = reader.records(ancount)?;
+        let authorities = reader.records(nscount)?;
+        let additionals = reader.records(arcount)?;
+
+        Ok(Message {
+            header: Header::from_flags(id, flags),
+            questions,
+            answers,
+            authorities,
+            additionals,
+        })
+    }
+}
+
+fn write_name(buf: &mut Vec<u8>, name: &str) -> Result<(), DnsError> {
+    let name = name.trim_end_matches('.');
+    if name.len() > 253 {
+        return Err(DnsError::Malformed("name longer than 253 bytes"));
+    }
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        if label.len() > 63 {
+            return Err(DnsError::Malformed("label longer than 63 bytes"));
+        }
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+    Ok(())
+}
+
+fn write_record(buf: &mut Vec<u8>, record: &Record) -> Result<(), DnsError> {
+    write_name(buf, &record.name)?;
+    buf.extend_from_slice(&u16::from(record.rtype).to_be_bytes());
+    buf.extend_from_slice(&record.class.to_be_bytes());
+    buf.extend_from_slice(&record.ttl.to_be_bytes());
+
+    let mut rdata = Vec::new();
+    match &record.data {
+        RecordData::A(ip) => rdata.extend_from_slice(&ip.octets()),
+        RecordData::Aaaa(ip) => rdata.extend_from_slice(&ip.octets()),
+        RecordData::Cname(name) | RecordData::Ptr(name) => write_name(&mut rdata, name)?,
+        RecordData::Srv {
+            priority,
+            weight,
+            port,
+            target,
+        } => {
+            rdata.extend_from_slice(&priority.to_be_bytes());
+            rdata.extend_from_slice(&weight.to_be_bytes());
+            rdata.extend_from_slice(&port.to_be_bytes());
+            write_name(&mut rdata, target)?;
+        }
+        RecordData::Txt(strings) => {
+            for string in strings {
+                let len = u8::try_from(string.len())
+                    .map_err(|_| DnsError::Malformed("TXT string longer than 255 bytes"))?;
+                rdata.push(len);
+                rdata.extend_from_slice(string);
+            }
+        }
+        RecordData::Other(data) => rdata.extend_from_slice(data),
+    }
+    let rdlength = u16::try_from(rdata.len()).map_err(|_| DnsError::Malformed("RDATA too long"))?;
+    buf.extend_from_slice(&rdlength.to_be_bytes());
+    buf.extend_from_slice(&rdata);
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], DnsError> {
+        let end = self.pos + len;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(DnsError::Malformed("message is too short"))?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, DnsError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DnsError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, DnsError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read a possibly compressed name, leaving `pos` right after it
+    fn name(&mut self) -> Result<String, DnsError> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut pos = self.pos;
+        // where to continue once we followed the first pointer
+        let mut resume = None;
+        let mut jumps = 0;
+        // on the wire, with the length bytes and the root, compression pointers followed
+        let mut wire_len = 1;
+
+        loop {
+            let len = *self
+                .bytes
+                .get(pos)
+                .ok_or(DnsError::Malformed("name runs past the end"))?;
+            match len {
+                0 => {
+                    pos += 1;
+                    break;
+                }
+                len if len & 0xc0 == 0xc0 => {
+                    let low = *self
+                        .bytes
+                        .get(pos + 1)
+                        .ok_or(DnsError::Malformed("pointer runs past the end"))?;
+                    jumps += 1;
+                    if jumps > MAX_POINTER_JUMPS {
+                        return Err(DnsError::Malformed("too many compression pointers"));
+                    }
+                    resume.get_or_insert(pos + 2);
+                    pos = ((len as usize & 0x3f) << 8) | low as usize;
+                }
+                len if len & 0xc0 == 0 => {
+                    let label = self
+                        .bytes
+                        .get(pos + 1..pos + 1 + len as usize)
+                        .ok_or(DnsError::Malformed("label runs past the end"))?;
+                    wire_len += 1 + label.len();
+                    if wire_len > MAX_NAME_WIRE_LEN {
+                        return Err(DnsError::Malformed("name longer than 255 bytes"));
+                    }
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    pos += 1 + len as usize;
+                }
+                _ => return Err(DnsError::Malformed("unknown label type")),
+            }
+        }
+
+        self.pos = resume.unwrap_or(pos);
+        Ok(labels.join("."))
+    }
+
+    fn records(&mut self, count: u16) -> Result<Vec<Record>, DnsError> {
+        (0..count).map(|_| self.record()).collect()
+    }
+
+    fn record(&mut self) -> Result<Record, DnsError> {
+        let name = self.name()?;
+        let rtype: RecordType = self.u16()?.into();
+        let class = self.u16()?;
+        let ttl = self.u32()?;
+        let rdlength = self.u16()? as usize;
+        let end = self.pos + rdlength;
+        if end > self.bytes.len() {
+            return Err(DnsError::Malformed("RDATA runs past the end"));
+        }
+
+        let data = match rtype {
+            RecordType::A if rdlength == 4 => {
+                let b = self.take(4)?;
+                RecordData::A(Ipv4Addr::new(b[0], b[1], b[2], b[3]))
+            }
+            RecordType::Aaaa if rdlength == 16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(self.take(16)?);
+                RecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            RecordType::Cname => RecordData::Cname(self.name()?),
+            RecordType::Ptr => RecordData::Ptr(self.name()?),
+            RecordType::Srv => RecordData::Srv {
+                priority: self.u16()?,
+                weight: self.u16()?,
+                port: self.u16()?,
+                target: self.name()?,
+            },
+            RecordType::Txt => {
+                let mut strings = Vec::new();
+                while self.pos < end {
+                    let len = self.u8()? as usize;
+                    strings.push(self.take(len)?.to_vec());
+                }
+                RecordData::Txt(strings)
+            }
+            _ => RecordData::Other(self.take(rdlength)?.to_vec()),
+        };
+
+        if self.pos != end {
+            return Err(DnsError::Malformed(
+                "RDATA length doesn't match its content",
+            ));
+        }
+        Ok(Record {
+            name,
+            rtype,
+            class,
+            ttl,
+            data,
+        })
+    }
+}
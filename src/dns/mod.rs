@@ -0,0 +1,371 @@
+//! A small stub resolver speaking the DNS wire format, as an alternative to
+//! going through glibc with `getaddrinfo`.
+//!
+//! Queries go over UDP first, and are retried over TCP when the answer is truncated.
+//! [`ResolverChain`] puts the hosts file in front, like glibc does with `nsswitch.conf`.
+
+mod message;
+pub use message::{Header, Message, Question, Rcode, Record, RecordData, RecordType, CLASS_IN};
+
+mod resolv_conf;
+pub use resolv_conf::{ResolvConf, RESOLV_CONF};
+
+mod hosts;
+pub use hosts::{parse_scoped_ip, Hosts, HostsEntry, HOSTS_FILE};
+
+mod chain;
+pub use chain::{parse_nsswitch_hosts, ResolverChain, Source, NSSWITCH_CONF};
+
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
+    path::Path,
+    sync::atomic::{AtomicU16, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{resolver::ResolveError, types::Family};
+
+// Big enough for EDNS sized answers, plain DNS over UDP is limited to 512 bytes
+const MAX_UDP_SIZE: usize = 4096;
+
+// Stop following CNAMEs after this many, there is probably a loop
+const MAX_CNAME_CHAIN: usize = 16;
+
+#[derive(Debug)]
+pub enum DnsError {
+    Io(io::Error),
+    /// No nameserver answered in time
+    Timeout,
+    /// The message can't be encoded or decoded
+    Malformed(&'static str),
+    /// `NXDOMAIN`: the name doesn't exist
+    NxDomain(String),
+    /// The name exists but has no record of the requested type
+    NoData(String),
+    /// The nameserver answered with an error code
+    Server(Rcode),
+    /// There is no nameserver to ask
+    NoNameservers,
+}
+
+impl Display for DnsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsError::Io(e) => write!(f, "{}", e),
+            DnsError::Timeout => write!(f, "Timed out waiting for the nameservers"),
+            DnsError::Malformed(reason) => write!(f, "Malformed DNS message: {}", reason),
+            DnsError::NxDomain(name) => write!(f, "No such domain: {}", name),
+            DnsError::NoData(name) => write!(f, "No address for {}", name),
+            DnsError::Server(rcode) => write!(f, "Nameserver error: {:?}", rcode),
+            DnsError::NoNameservers => write!(f, "No nameserver configured"),
+        }
+    }
+}
+
+impl Error for DnsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DnsError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DnsError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => DnsError::Timeout,
+            _ => DnsError::Io(e),
+        }
+    }
+}
+
+/// Map to the closest `EAI_*` error, like glibc does for its own DNS failures
+impl From<DnsError> for ResolveError {
+    fn from(e: DnsError) -> Self {
+        match e {
+            DnsError::NxDomain(_) => ResolveError::NoName,
+            DnsError::NoData(_) => ResolveError::NoData,
+            DnsError::Timeout | DnsError::Server(Rcode::ServFail) => ResolveError::Again,
+            DnsError::Io(e) => match e.raw_os_error() {
+                Some(errno) => ResolveError::System(nix::errno::Errno::from_raw(errno)),
+                None => ResolveError::Fail,
+            },
+            DnsError::Malformed(_) | DnsError::Server(_) | DnsError::NoNameservers => {
+                ResolveError::Fail
+            }
+        }
+    }
+}
+
+/// Result of [`Resolver::lookup_ip`] and [`Hosts::lookup_ip`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpLookup {
+    /// The name at the end of the CNAME chain
+    pub canonname: String,
+    /// Port is 0, link-local IPv6 addresses keep their scope id
+    pub addrs: Vec<SocketAddr>,
+}
+
+impl IpLookup {
+    /// The addresses, ready to connect to `port`
+    pub fn with_port(&self, port: u16) -> Vec<SocketAddr> {
+        self.addrs
+            .iter()
+            .map(|addr| {
+                let mut addr = *addr;
+                addr.set_port(port);
+                addr
+            })
+            .collect()
+    }
+}
+
+/// Stub resolver: it asks a recursive nameserver and doesn't recurse itself
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    conf: ResolvConf,
+}
+
+impl Resolver {
+    pub fn new(conf: ResolvConf) -> Self {
+        Resolver { conf }
+    }
+
+    /// Use the system configuration from `/etc/resolv.conf`, see [`Resolver::from_file`]
+    pub fn from_system() -> Result<Self, DnsError> {
+        Self::from_file(RESOLV_CONF)
+    }
+
+    /// Use the configuration in `path`.
+    ///
+    /// Like glibc, fall back to a nameserver on localhost when there is none,
+    /// or when the file doesn't exist.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, DnsError> {
+        let mut conf = match ResolvConf::from_file(path) {
+            Ok(conf) => conf,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => ResolvConf::default(),
+            Err(e) => return Err(e.into()),
+        };
+        if conf.nameservers.is_empty() {
+            conf.nameservers
+                .push(SocketAddr::from((Ipv4Addr::LOCALHOST, 53)));
+        }
+        Ok(Resolver::new(conf))
+    }
+
+    pub fn conf(&self) -> &ResolvConf {
+        &self.conf
+    }
+
+    /// Send a single query for `name`, trying every nameserver in turn
+    pub fn query(&self, name: &str, qtype: RecordType) -> Result<Message, DnsError> {
+        let mut last_error = DnsError::NoNameservers;
+        for _ in 0..self.conf.attempts.max(1) {
+            for &server in &self.conf.nameservers {
+                let query = Message::query(next_id(), name, qtype);
+                match self.exchange(server, &query) {
+                    Ok(response) => match response.header.rcode {
+                        Rcode::NoError => return Ok(response),
+                        Rcode::NxDomain => return Err(DnsError::NxDomain(name.to_string())),
+                        // another server might do better
+                        rcode => last_error = DnsError::Server(rcode),
+                    },
+                    Err(e) => last_error = e,
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Like [`Resolver::query`], but also tries the search domains for short names
+    pub fn lookup(&self, name: &str, qtype: RecordType) -> Result<Message, DnsError> {
+        let mut last_error = None;
+        for candidate in self.candidates(name) {
+            match self.query(&candidate, qtype) {
+                Ok(response) => return Ok(response),
+                Err(e @ DnsError::NxDomain(_)) => last_error = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| DnsError::NxDomain(name.to_string())))
+    }
+
+    /// Resolve a host to its addresses, AAAA first, following CNAMEs.
+    ///
+    /// With [`Family::Unspecified`], a failed AAAA query doesn't stop us from asking for A:
+    /// the first error is only returned when we found no address at all.
+    pub fn lookup_ip(&self, host: &str, family: Family) -> Result<IpLookup, DnsError> {
+        // numeric hosts don't need a nameserver
+        if let Some(addr) = parse_scoped_ip(host) {
+            return Ok(IpLookup {
+                canonname: host.to_string(),
+                addrs: vec![addr],
+            });
+        }
+
+        let qtypes: &[RecordType] = match family {
+            Family::Ipv4 => &[RecordType::A],
+            Family::Ipv6 => &[RecordType::Aaaa],
+            Family::Unspecified => &[RecordType::Aaaa, RecordType::A],
+        };
+
+        let mut canonname = None;
+        let mut addrs = Vec::new();
+        let mut first_error = None;
+        for &qtype in qtypes {
+            let response = match self.lookup(host, qtype) {
+                Ok(response) => response,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                    continue;
+                }
+            };
+            let question = response
+                .questions
+                .first()
+                .map_or(host, |question| question.name.as_str());
+            let name = follow_cnames(&response.answers, question);
+            addrs.extend(
+                response
+                    .answers
+                    .iter()
+                    .filter(|record| record.name.eq_ignore_ascii_case(&name))
+                    .filter_map(|record| match record.data {
+                        RecordData::A(ip) => Some(SocketAddr::from((ip, 0))),
+                        RecordData::Aaaa(ip) => Some(SocketAddr::from((ip, 0))),
+                        _ => None,
+                    }),
+            );
+            canonname.get_or_insert(name);
+        }
+
+        if addrs.is_empty() {
+            return Err(first_error.unwrap_or_else(|| DnsError::NoData(host.to_string())));
+        }
+        Ok(IpLookup {
+            canonname: canonname.unwrap_or_else(|| host.to_string()),
+            addrs,
+        })
+    }
+
+    /// Names to try in order, see `ndots` in resolv.conf(5)
+    fn candidates(&self, name: &str) -> Vec<String> {
+        if name.ends_with('.') || self.conf.search.is_empty() {
+            return vec![name.to_string()];
+        }
+        let searched = self
+            .conf
+            .search
+            .iter()
+            .map(|domain| format!("{}.{}", name, domain));
+        if name.matches('.').count() >= self.conf.ndots {
+            std::iter::once(name.to_string()).chain(searched).collect()
+        } else {
+            searched.chain(std::iter::once(name.to_string())).collect()
+        }
+    }
+
+    /// One query to one server, over UDP then TCP if the answer was truncated
+    fn exchange(&self, server: SocketAddr, query: &Message) -> Result<Message, DnsError> {
+        let bytes = query.to_bytes()?;
+        let response = self.exchange_udp(server, query, &bytes)?;
+        if response.header.tc {
+            return self.exchange_tcp(server, query, &bytes);
+        }
+        Ok(response)
+    }
+
+    fn exchange_udp(
+        &self,
+        server: SocketAddr,
+        query: &Message,
+        bytes: &[u8],
+    ) -> Result<Message, DnsError> {
+        let local: IpAddr = match server {
+            SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let socket = UdpSocket::bind((local, 0))?;
+        // connecting makes the kernel drop datagrams from anybody else
+        socket.connect(server)?;
+        socket.set_read_timeout(Some(self.conf.timeout))?;
+        socket.send(bytes)?;
+
+        let mut buf = [0u8; MAX_UDP_SIZE];
+        loop {
+            let len = socket.recv(&mut buf)?;
+            match Message::parse(&buf[..len]) {
+                Ok(response) if is_answer_to(&response, query) => return Ok(response),
+                // late answers to an older query, or garbage: keep waiting
+                _ => continue,
+            }
+        }
+    }
+
+    fn exchange_tcp(
+        &self,
+        server: SocketAddr,
+        query: &Message,
+        bytes: &[u8],
+    ) -> Result<Message, DnsError> {
+        let mut stream = TcpStream::connect_timeout(&server, self.conf.timeout)?;
+        stream.set_read_timeout(Some(self.conf.timeout))?;
+        stream.set_write_timeout(Some(self.conf.timeout))?;
+
+        // over TCP, every message is prefixed with its length
+        let len = u16::try_from(bytes.len()).map_err(|_| DnsError::Malformed("query too long"))?;
+        stream.write_all(&len.to_be_bytes())?;
+        stream.write_all(bytes)?;
+
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len)?;
+        let mut buf = vec![0u8; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut buf)?;
+
+        let response = Message::parse(&buf)?;
+        if !is_answer_to(&response, query) {
+            return Err(DnsError::Malformed("answer doesn't match the query"));
+        }
+        Ok(response)
+    }
+}
+
+fn is_answer_to(response: &Message, query: &Message) -> bool {
+    response.header.qr
+        && response.header.id == query.header.id
+        && response.questions.len() == query.questions.len()
+        && response
+            .questions
+            .iter()
+            .zip(&query.questions)
+            .all(|(a, b)| a.name.eq_ignore_ascii_case(&b.name) && a.qtype == b.qtype)
+}
+
+/// Walk the CNAME records starting at `name`, returning the last name of the chain
+fn follow_cnames(answers: &[Record], name: &str) -> String {
+    let mut name = name.to_string();
+    for _ in 0..MAX_CNAME_CHAIN {
+        let target = answers.iter().find_map(|record| match &record.data {
+            RecordData::Cname(target) if record.name.eq_ignore_ascii_case(&name) => Some(target),
+            _ => None,
+        });
+        match target {
+            Some(target) => name = target.clone(),
+            None => break,
+        }
+    }
+    name
+}
+
+/// Query ids should be hard to guess, this is good enough for a learning project
+fn next_id() -> u16 {
+    static COUNTER: AtomicU16 = AtomicU16::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    (nanos as u16) ^ ((nanos >> 16) as u16) ^ COUNTER.fetch_add(0x9e37, Ordering::Relaxed)
+}
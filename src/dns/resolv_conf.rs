@@ -0,0 +1,80 @@
+use std::{fs, io, net::SocketAddr, path::Path, time::Duration};
+
+use super::parse_scoped_ip;
+
+/// Where glibc reads its configuration from
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// glibc's `RES_MAXRETRANS`, the longest `options timeout:` in seconds
+const MAX_TIMEOUT: u64 = 30;
+
+/// The parts of `resolv.conf(5)` the builtin resolver understands
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvConf {
+    pub nameservers: Vec<SocketAddr>,
+    /// Domains appended to names with less than `ndots` dots
+    pub search: Vec<String>,
+    pub ndots: usize,
+    /// How long to wait for each nameserver
+    pub timeout: Duration,
+    /// How many times to go through the list of nameservers
+    pub attempts: usize,
+}
+
+impl Default for ResolvConf {
+    fn default() -> Self {
+        ResolvConf {
+            nameservers: Vec::new(),
+            search: Vec::new(),
+            ndots: 1,
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+        }
+    }
+}
+
+impl ResolvConf {
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Parse the file contents, ignoring anything we don't understand like glibc does
+    pub fn parse(contents: &str) -> Self {
+        let mut conf = ResolvConf::default();
+        for line in contents.lines() {
+            let line = line.split(['#', ';']).next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    // fe80::1%eth0 keeps its zone as scope id, an unknown zone drops the server
+                    if let Some(mut addr) = words.next().and_then(parse_scoped_ip) {
+                        addr.set_port(53);
+                        conf.nameservers.push(addr);
+                    }
+                }
+                // the last one of `domain` and `search` wins
+                Some("domain") => conf.search = words.take(1).map(String::from).collect(),
+                Some("search") => conf.search = words.map(String::from).collect(),
+                Some("options") => {
+                    for option in words {
+                        match option.split_once(':') {
+                            Some(("ndots", n)) => conf.ndots = n.parse().unwrap_or(conf.ndots),
+                            Some(("timeout", n)) => {
+                                // like glibc: at least a second, at most RES_MAXRETRANS
+                                if let Ok(secs) = n.parse::<u64>() {
+                                    conf.timeout = Duration::from_secs(secs.clamp(1, MAX_TIMEOUT));
+                                }
+                            }
+                            Some(("attempts", n)) => {
+                                conf.attempts = n.parse().unwrap_or(conf.attempts)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        conf
+    }
+}
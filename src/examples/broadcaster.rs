@@ -1,8 +1,11 @@
 use std::{
     net::{Ipv4Addr, SocketAddrV4},
     os::fd::AsRawFd,
+    process,
 };
 
+use crate::interfaces;
+
 /// Section 7.7 "Broadcast Packets—Hello, World!"
 ///
 /// Broadcast client
@@ -13,11 +16,55 @@ use std::{
 ///
 /// Protocol: `UDP`
 ///
+/// Instead of knowing the broadcast address, we can ask for the one of an `interface`:
+/// the subnet comes from the netmask that `getifaddrs` reports.
+/// With `all_interfaces`, the message goes to every subnet we are on that supports broadcast.
+/// A subnet we can't send to is reported and skipped, we only fail when nothing was sent.
+///
+/// ```console
+/// beej-rs broadcast-listener
+/// beej-rs broadcaster --interface eth0 "Hello, World!"
+/// ```
 ///
 /// Original: [broadcaster.c](https://beej.us/guide/bgnet/examples/broadcaster.c)
-pub fn broadcaster(host: Ipv4Addr, port: u16, message: String) {
-    let localhost = SocketAddrV4::new(host, port);
-    let socket: nix::sys::socket::SockaddrIn = localhost.into();
+pub fn broadcaster(
+    host: Ipv4Addr,
+    port: u16,
+    message: String,
+    interface: Option<String>,
+    all_interfaces: bool,
+) {
+    let hosts = if all_interfaces {
+        let interfaces = interfaces::ipv4_interfaces().unwrap_or_else(|e| {
+            eprintln!("broadcaster: getifaddrs: {}", e);
+            process::exit(1);
+        });
+        let hosts: Vec<Ipv4Addr> = interfaces
+            .iter()
+            .filter(|i| i.can_broadcast)
+            .map(|i| i.broadcast())
+            .collect();
+        if hosts.is_empty() {
+            eprintln!("broadcaster: no interface with a broadcast address");
+            process::exit(2);
+        }
+        hosts
+    } else if let Some(name) = interface {
+        match interfaces::ipv4_interface(&name) {
+            Ok(Some(interface)) => vec![interface.broadcast()],
+            Ok(None) => {
+                eprintln!("broadcaster: no IPv4 interface named {}", name);
+                process::exit(2);
+            }
+            Err(e) => {
+                eprintln!("broadcaster: getifaddrs: {}", e);
+                process::exit(1);
+            }
+        }
+    } else {
+        vec![host]
+    };
+
     let sockfd = nix::sys::socket::socket(
         nix::sys::socket::AddressFamily::Inet,
         nix::sys::socket::SockType::Datagram,
@@ -29,12 +76,23 @@ pub fn broadcaster(host: Ipv4Addr, port: u16, message: String) {
     // Set the socket as Broadcast
     nix::sys::socket::setsockopt(&sockfd, nix::sys::socket::sockopt::Broadcast, &true)
         .expect("Failed to set socket options");
-    println!("Sending message to {}", localhost);
-    nix::sys::socket::sendto(
-        sockfd.as_raw_fd(),
-        message.as_bytes(),
-        &socket,
-        nix::sys::socket::MsgFlags::empty(),
-    )
-    .expect("Failed to send message");
+    let mut sent = 0;
+    for host in hosts {
+        let addr = SocketAddrV4::new(host, port);
+        let socket: nix::sys::socket::SockaddrIn = addr.into();
+        println!("Sending message to {}", addr);
+        // an interface that is down is no reason to skip the others
+        match nix::sys::socket::sendto(
+            sockfd.as_raw_fd(),
+            message.as_bytes(),
+            &socket,
+            nix::sys::socket::MsgFlags::empty(),
+        ) {
+            Ok(_) => sent += 1,
+            Err(e) => eprintln!("broadcaster: sendto {}: {}", addr, e),
+        }
+    }
+    if sent == 0 {
+        process::exit(1);
+    }
 }
@@ -0,0 +1,60 @@
+use std::{
+    net::{Ipv4Addr, SocketAddrV4},
+    os::fd::AsRawFd,
+};
+
+use nix::sys::socket::{self, sockopt, AddressFamily, SockFlag, SockType, SockaddrIn};
+
+use crate::shutdown::{Event, Shutdown};
+
+/// Section 7.7 "Broadcast Packets—Hello, World!"
+///
+/// Counterpart of [`broadcaster`](super::broadcaster)
+///
+/// IPv4 Only
+///
+/// Bindings: `nix`
+///
+/// Protocol: `UDP`
+///
+/// The book receives broadcasts with `listener`, this one is ready for them:
+/// it binds to `INADDR_ANY`, since a broadcast is not sent to any of our addresses,
+/// and sets `SO_BROADCAST` and `SO_REUSEADDR`, so several listeners on the host can share the port.
+/// Each of them gets a copy of every broadcast.
+///
+/// `SIGINT` or `SIGTERM` stops listening.
+///
+/// ```console
+/// beej-rs broadcast-listener
+/// beej-rs broadcaster --all-interfaces "Hello, World!"
+/// ```
+pub fn broadcastlistener(port: u16) {
+    // Readable once we are asked to stop
+    let shutdown = Shutdown::install().expect("Failed to install signal handlers");
+
+    let sockfd = socket::socket(
+        AddressFamily::Inet,
+        SockType::Datagram,
+        SockFlag::empty(),
+        None,
+    )
+    .expect("Failed to create socket");
+    socket::setsockopt(&sockfd, sockopt::ReuseAddr, &true).expect("Failed to set SO_REUSEADDR");
+    socket::setsockopt(&sockfd, sockopt::Broadcast, &true).expect("Failed to set SO_BROADCAST");
+
+    let local_addr: SockaddrIn = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into();
+    socket::bind(sockfd.as_raw_fd(), &local_addr).expect("Failed to bind to socket");
+    println!("Listening for broadcasts on {}", local_addr);
+
+    while shutdown.wait(&sockfd).expect("poll failed") == Event::Readable {
+        let mut buf = [0u8; 1024];
+        let (len, addr) =
+            socket::recvfrom::<SockaddrIn>(sockfd.as_raw_fd(), &mut buf).expect("recvfrom failed");
+        let from = addr.map(|a| a.to_string()).unwrap_or_default();
+        println!(
+            "got packet from {}: {:?}",
+            from,
+            String::from_utf8_lossy(&buf[..len])
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    io::{self, Write},
+    os::fd::{AsFd, AsRawFd},
+    process,
+};
+
+use nix::{
+    errno::Errno,
+    poll::{PollFd, PollFlags, PollTimeout},
+};
+
+use crate::{
+    builders::AddrInfo,
+    partial,
+    resolver::{self, ResolveError},
+    socket::Socket,
+    types::{Family, SocketType},
+};
+
+/// Counterpart of the chat servers of sections 7.2 and 7.3
+///
+/// Bindings: `nix` for `poll`, `libc` through [`Socket`]
+///
+/// Protocol: `TCP`
+///
+/// Instead of `telnet`, we `poll` stdin and the socket together:
+/// what we type is sent to the server, and what the other users type is printed.
+/// We stop on EOF on stdin (CTRL+D), or when the server closes the connection.
+///
+/// ```console
+/// beej-rs chat-server
+/// beej-rs chat-client localhost
+/// ```
+pub fn chatclient(host: String, port: u16, family: Family) -> Result<(), ResolveError> {
+    let hints = AddrInfo::builder()
+        .family(family)
+        .socktype(SocketType::Stream)
+        .build();
+    let servinfo = resolver::getaddrinfo(Some(&host), Some(&port.to_string()), hints)?;
+
+    // connect to the first result we can
+    let mut sockfd = None;
+    for entry in servinfo.iter() {
+        let (ai_family, ai_socktype, ai_protocol) = entry.socket_args();
+        let Ok(socket) = Socket::from_args(ai_family, ai_socktype, ai_protocol) else {
+            continue;
+        };
+        if let Err(e) = socket.connect(entry.sockaddr()) {
+            eprintln!("chat-client: connect err: {}", e);
+            continue;
+        }
+        sockfd = Some(socket);
+        break;
+    }
+    let Some(sockfd) = sockfd else {
+        eprintln!("chat-client: failed to connect");
+        process::exit(2);
+    };
+    eprintln!(
+        "chat-client: connected to {}:{}, CTRL+D to quit",
+        host, port
+    );
+
+    // we read stdin without buffering, so poll never misses a line sitting in a buffer
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().lock();
+    let mut buf = [0u8; 1024];
+    loop {
+        let mut pfds = [
+            PollFd::new(stdin.as_fd(), PollFlags::POLLIN),
+            PollFd::new(sockfd.as_fd(), PollFlags::POLLIN),
+        ];
+        match nix::poll::poll(&mut pfds, PollTimeout::NONE) {
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(e) => panic!("poll failed: {}", e),
+        }
+        // POLLHUP counts too, the read tells us what happened
+        let stdin_ready = pfds[0].any().unwrap_or_default();
+        let socket_ready = pfds[1].any().unwrap_or_default();
+
+        // print what the others said first, it might be the server saying goodbye
+        if socket_ready {
+            match sockfd.recv(&mut buf) {
+                Ok(0) => {
+                    eprintln!("chat-client: server closed the connection");
+                    return Ok(());
+                }
+                Ok(nbytes) => {
+                    stdout
+                        .write_all(&buf[..nbytes])
+                        .and_then(|()| stdout.flush())
+                        .expect("write failed");
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    eprintln!("chat-client: recv err: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        if stdin_ready {
+            let nbytes = match nix::unistd::read(stdin.as_raw_fd(), &mut buf) {
+                Ok(nbytes) => nbytes,
+                Err(Errno::EINTR) => continue,
+                Err(e) => panic!("read failed: {}", e),
+            };
+            // CTRL+D, we are done and the server sees us leave when the socket is dropped
+            if nbytes == 0 {
+                return Ok(());
+            }
+            if let Err(e) = partial::sendall(&sockfd, &buf[..nbytes]) {
+                eprintln!("chat-client: send err: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
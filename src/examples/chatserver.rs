@@ -0,0 +1,331 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    net::{Ipv6Addr, SocketAddrV6},
+    os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use nix::{
+    errno::Errno,
+    sys::{
+        socket::MsgFlags,
+        time::{TimeVal, TimeValLike},
+    },
+};
+
+use crate::{
+    framing::{FrameCodec, FrameError},
+    partial,
+    reactor::{self, Event, EventLoop, Interest},
+    shutdown::{self, Shutdown},
+    types::{Backend, Framing},
+};
+
+/// How far behind a client can fall before it is disconnected
+const MAX_OUTBOX: usize = 1 << 20;
+
+/// A connected client, what it sent that isn't a whole message yet,
+/// and what we relayed to it that its socket couldn't take yet
+struct Client {
+    fd: OwnedFd,
+    codec: FrameCodec,
+    outbox: Vec<u8>,
+    /// Registered for `WRITABLE` too, because the outbox isn't empty
+    waiting: bool,
+}
+
+impl Client {
+    fn new(fd: OwnedFd, codec: FrameCodec) -> Self {
+        Client {
+            fd,
+            codec,
+            outbox: Vec::new(),
+            waiting: false,
+        }
+    }
+
+    /// Send what the socket takes of the outbox without blocking,
+    /// and wait for room in the send buffer if some is left
+    fn flush(&mut self, event_loop: &mut dyn EventLoop) -> io::Result<()> {
+        while !self.outbox.is_empty() {
+            match nix::sys::socket::send(
+                self.fd.as_raw_fd(),
+                &self.outbox,
+                MsgFlags::MSG_DONTWAIT | MsgFlags::MSG_NOSIGNAL,
+            ) {
+                Ok(n) => drop(self.outbox.drain(..n)),
+                Err(Errno::EINTR) => {}
+                Err(Errno::EAGAIN) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if self.outbox.len() > MAX_OUTBOX {
+            return Err(io::Error::other(format!(
+                "{} bytes it doesn't read",
+                self.outbox.len()
+            )));
+        }
+
+        let waiting = !self.outbox.is_empty();
+        if waiting != self.waiting {
+            let interest = if waiting {
+                Interest::READABLE | Interest::WRITABLE
+            } else {
+                Interest::READABLE
+            };
+            event_loop.reregister(self.fd.as_fd(), interest)?;
+            self.waiting = waiting;
+        }
+        Ok(())
+    }
+}
+
+/// The bytes of `goodbye` for the clients of `codec`, checked before we start instead of
+/// failing when we stop
+pub fn goodbye_message(codec: &FrameCodec, goodbye: &str) -> Result<Vec<u8>, FrameError> {
+    match codec.framing() {
+        Framing::Raw => Ok(goodbye.as_bytes().to_vec()),
+        // the frame says where the message ends, no need for the newline
+        _ => codec.encode(goodbye.trim_end_matches('\n').as_bytes()),
+    }
+}
+
+/// Forget the client on `fd`, dropping it closes its fd
+fn disconnect(event_loop: &mut dyn EventLoop, clients: &mut BTreeMap<RawFd, Client>, fd: RawFd) {
+    let client = clients.remove(&fd).expect("client is connected");
+    event_loop
+        .deregister(client.fd.as_fd())
+        .expect("client was registered");
+}
+
+/// Sections 7.2 "poll() - Synchonous I/O Multiplexing" and 7.3 "select()—Synchronous I/O
+/// Multiplexing, Old School"
+///
+/// Simple multi-user chat server
+///
+/// Bindings: `nix`, through [`reactor`]
+///
+/// Protocol: `TCP`
+///
+/// The book writes this server twice, once with `poll` and once with `select`.
+/// Here the waiting is done by the [`reactor::EventLoop`] of `backend`, `epoll` included,
+/// and the relay is written once: what a client sends is sent to all the others.
+///
+/// ```console
+/// beej-rs chat-server --backend epoll
+/// beej-rs chat-client localhost
+/// ```
+///
+/// or `telnet localhost 9034`, then CTRL+5 and "quit" to close the connection
+///
+/// Each client gets a clone of `codec`, so we relay whole messages instead of whatever `recv`
+/// returned. A client sending a frame we can't decode, or failing to `recv`, is disconnected.
+///
+/// A client that doesn't read can't stop the others from chatting: we never block in `send`.
+/// What its socket can't take is queued, and sent when the event loop says it's writable.
+/// Past [`MAX_OUTBOX`] queued bytes, the client is disconnected.
+///
+/// On `SIGINT` or `SIGTERM` we stop accepting, send `goodbye` to the clients and return,
+/// it's already encoded by [`goodbye_message`].
+/// The queued messages go first, and a client has [`shutdown::DRAIN_TIMEOUT`] to take them.
+///
+/// Original: [pollserver.c](https://beej.us/guide/bgnet/examples/pollserver.c) and
+/// [selectserver.c](https://beej.us/guide/bgnet/examples/selectserver.c)
+pub fn chatserver(port: u16, goodbye: Vec<u8>, codec: FrameCodec, backend: Backend) {
+    // [::] takes the IPv4 clients too, as IPv4-mapped addresses
+    let unspec = SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0);
+    let socket: nix::sys::socket::SockaddrIn6 = unspec.into();
+    let listener = nix::sys::socket::socket(
+        nix::sys::socket::AddressFamily::Inet6,
+        nix::sys::socket::SockType::Stream,
+        nix::sys::socket::SockFlag::empty(),
+        None,
+    )
+    .expect("Failed to create socket");
+
+    // Lose the "address already in use" error message
+    nix::sys::socket::setsockopt(&listener, nix::sys::socket::sockopt::ReuseAddr, &true)
+        .expect("Failed to set socket options");
+
+    nix::sys::socket::bind(listener.as_raw_fd(), &socket).expect("Failed to bind to socket");
+    let backlog = nix::sys::socket::Backlog::new(10).expect("Failed to create backlog");
+    nix::sys::socket::listen(&listener, backlog).expect("Failed to listen on socket");
+
+    // Readable once we are asked to stop
+    let shutdown = Shutdown::install().expect("Failed to install signal handlers");
+
+    let mut event_loop = reactor::new(backend).expect("Failed to create the event loop");
+    event_loop
+        .register(listener.as_fd(), Interest::READABLE)
+        .expect("Failed to watch the listener");
+    event_loop
+        .register(shutdown.as_fd(), Interest::READABLE)
+        .expect("Failed to watch the shutdown pipe");
+    // ordered by fd, so the relay order doesn't depend on the backend
+    let mut clients: BTreeMap<RawFd, Client> = BTreeMap::new();
+
+    println!("Listening on {} with {}", unspec, backend);
+
+    let mut events: Vec<Event> = Vec::new();
+    loop {
+        match event_loop.wait(&mut events, None) {
+            Ok(()) => {}
+            // the backends report EINTR as no events, in case one doesn't
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // the clients still get the goodbye
+            Err(e) => {
+                eprintln!("[Server] {} failed, stopping: {}", backend, e);
+                break;
+            }
+        }
+        // stopping wins over the last messages
+        if events.iter().any(|e| e.fd == shutdown.as_fd().as_raw_fd()) {
+            break;
+        }
+
+        for event in &events {
+            if event.writable {
+                // closed earlier in this round otherwise
+                if let Some(client) = clients.get_mut(&event.fd) {
+                    if let Err(e) = client.flush(event_loop.as_mut()) {
+                        println!("[Client] send on fd {} failed, closing: {}", event.fd, e);
+                        disconnect(event_loop.as_mut(), &mut clients, event.fd);
+                        continue;
+                    }
+                }
+            }
+            if !event.readable {
+                continue;
+            }
+            if event.fd == listener.as_raw_fd() {
+                // a client giving up before we accept is no reason to stop
+                let new_fd = match nix::sys::socket::accept(listener.as_raw_fd()) {
+                    // SAFETY: accept() just gave us this fd
+                    Ok(new_fd) => unsafe { OwnedFd::from_raw_fd(new_fd) },
+                    Err(e) => {
+                        eprintln!("accept: {}", e);
+                        continue;
+                    }
+                };
+                if let Err(e) = event_loop.register(new_fd.as_fd(), Interest::READABLE) {
+                    // select can't watch it, closing is all we can do
+                    eprintln!("[Server] Can't watch the new connection: {}", e);
+                    continue;
+                }
+                match nix::sys::socket::getpeername::<nix::sys::socket::SockaddrStorage>(
+                    new_fd.as_raw_fd(),
+                ) {
+                    Ok(ss) => println!(
+                        "[Server] New connection from {} on fd {}",
+                        ss,
+                        new_fd.as_raw_fd()
+                    ),
+                    Err(_) => println!("[Server] New connection on fd {}", new_fd.as_raw_fd()),
+                }
+                clients.insert(new_fd.as_raw_fd(), Client::new(new_fd, codec.clone()));
+                continue;
+            }
+
+            // closed earlier in this round
+            let Some(client) = clients.get_mut(&event.fd) else {
+                continue;
+            };
+            let mut buf = [0u8; 1024];
+            let mut closed = false;
+            let mut frames = Vec::new();
+            match nix::sys::socket::recv(
+                client.fd.as_raw_fd(),
+                &mut buf,
+                nix::sys::socket::MsgFlags::empty(),
+            ) {
+                Ok(0) => {
+                    println!("[Client] fd {} closed the connection", event.fd);
+                    closed = true;
+                }
+                Ok(nbytes) => {
+                    client.codec.push(&buf[..nbytes]);
+                    loop {
+                        match client.codec.decode() {
+                            Ok(Some(frame)) => frames.push(frame),
+                            Ok(None) => break,
+                            Err(e) => {
+                                println!("[Client] Bad frame from fd {}, closing: {}", event.fd, e);
+                                closed = true;
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(nix::errno::Errno::EINTR) => {}
+                // a reset connection only concerns this client
+                Err(e) => {
+                    println!("[Client] recv on fd {} failed, closing: {}", event.fd, e);
+                    closed = true;
+                }
+            }
+
+            // the frames that were complete are relayed, even from a client we are closing
+            let mut data = Vec::new();
+            for frame in &frames {
+                match codec.encode(frame) {
+                    Ok(encoded) => data.extend(encoded),
+                    // only the sender is at fault
+                    Err(e) => {
+                        println!("[Client] Can't relay from fd {}, closing: {}", event.fd, e);
+                        closed = true;
+                        break;
+                    }
+                }
+            }
+            let mut failed = Vec::new();
+            if !data.is_empty() {
+                for (&fd, target) in clients.iter_mut() {
+                    if fd == event.fd {
+                        continue;
+                    }
+                    target.outbox.extend_from_slice(&data);
+                    if let Err(e) = target.flush(event_loop.as_mut()) {
+                        println!("[Client] send on fd {} failed, closing: {}", fd, e);
+                        failed.push(fd);
+                    }
+                }
+            }
+            if closed {
+                failed.push(event.fd);
+            }
+            for fd in failed {
+                disconnect(event_loop.as_mut(), &mut clients, fd);
+            }
+        }
+    }
+
+    // Stop accepting, and tell the clients we are leaving
+    println!("Shutting down, saying goodbye to {} clients", clients.len());
+    drop(event_loop);
+    drop(listener);
+    let timeout = TimeVal::milliseconds(shutdown::DRAIN_TIMEOUT.as_millis() as i64);
+    let clients = clients
+        .into_values()
+        .filter_map(|client| {
+            // a client that doesn't read can't keep us longer than the drain
+            let _ = nix::sys::socket::setsockopt(
+                &client.fd,
+                nix::sys::socket::sockopt::SendTimeout,
+                &timeout,
+            );
+            // what it's still waiting for goes before the goodbye
+            match partial::sendall(&client.fd, &client.outbox) {
+                Ok(()) => Some(client.fd),
+                Err(e) => {
+                    println!(
+                        "[Client] fd {} missed the goodbye: {}",
+                        client.fd.as_raw_fd(),
+                        e
+                    );
+                    None
+                }
+            }
+        })
+        .collect();
+    shutdown::goodbye(clients, &goodbye, shutdown::DRAIN_TIMEOUT);
+}
@@ -1,116 +1,205 @@
 use std::{
-    ffi::{CStr, CString},
-    mem,
-    net::IpAddr,
-    ptr,
+    fs::File,
+    io::{self, Write},
+    net::SocketAddr,
+    path::Path,
+    process,
 };
 
+use socket2::SockAddr;
+
 use crate::{
-    builders::AddrInfo,
-    types::{Family, SocketType},
+    builders::{AddrInfo, StreamClient},
+    connect::{self, ConnectError},
+    dns,
+    resolver::{self, ResolveError},
+    socket::Socket,
+    types::{AddressFamily, Family, ResolverKind, SocketType},
 };
 
-// helper function
-fn showaddrinfo(addr: &libc::addrinfo) {
-    let ip = unsafe {
-        match (*addr).ai_family {
-            libc::AF_INET => {
-                let addr = (*addr).ai_addr as *const libc::sockaddr_in;
-                let addr = &*addr;
-                let ip = addr.sin_addr;
-                let ip = ip.s_addr;
-                let ip = ip.to_be();
-                let ip = IpAddr::V4(ip.into());
-                ip
+// helper function, on stderr so it doesn't mix with what we receive
+fn showaddrinfo(addr: &SocketAddr) {
+    eprintln!("IP: {:?}", addr.ip());
+}
+
+// glibc does the lookup
+fn system_lookup(
+    host: &str,
+    service: &str,
+    family: Family,
+) -> Result<Vec<SocketAddr>, ResolveError> {
+    let hints = AddrInfo::builder()
+        .family(family)
+        .socktype(SocketType::Stream)
+        .build();
+
+    let servinfo = resolver::getaddrinfo(Some(host), Some(service), hints)?;
+    Ok(servinfo
+        .iter()
+        .filter_map(|entry| entry.socket_addr())
+        .collect())
+}
+
+// our own resolver does the lookup, hosts file first then DNS
+fn builtin_lookup(
+    host: &str,
+    service: &str,
+    family: Family,
+    hosts_file: Option<&Path>,
+) -> Result<Vec<SocketAddr>, ResolveError> {
+    let port = resolver::getservbyname(service, SocketType::Stream)?;
+    let lookup = dns::ResolverChain::from_system(hosts_file).lookup_ip(host, family)?;
+    Ok(lookup.with_port(port))
+}
+
+// loop through all the results and connect to the first we can
+fn connect_sequential(addrs: &[SocketAddr]) -> Option<(Socket, SocketAddr)> {
+    for addr in addrs {
+        let sockaddr = SockAddr::from(*addr);
+        // an address we can't make a socket for doesn't stop us from trying the next one
+        let family = match AddressFamily::try_from(sockaddr.family() as libc::c_int) {
+            Ok(family) => family,
+            Err(e) => {
+                eprintln!("client: socket err: {}", e);
+                continue;
             }
-            libc::AF_INET6 => {
-                let addr = (*addr).ai_addr as *const libc::sockaddr_in6;
-                let addr = &*addr;
-                let ip = addr.sin6_addr;
-                let ip = ip.s6_addr;
-                let ip = IpAddr::V6(ip.into());
-                ip
+        };
+        let sockfd = match Socket::new(family, SocketType::Stream, None) {
+            Ok(sockfd) => sockfd,
+            Err(e) => {
+                eprintln!("client: socket err: {}", e);
+                continue;
             }
-            _ => {
-                panic!("Unknown family");
+        };
+
+        // a failed socket is closed when dropped
+        if let Err(e) = sockfd.connect(&sockaddr) {
+            eprintln!("client: connect err: {}", e);
+            continue;
+        }
+        return Some((sockfd, *addr));
+    }
+    None
+}
+
+// the connectors tell why each address failed
+fn report_attempts(
+    connected: Result<(Socket, SocketAddr), ConnectError>,
+) -> Option<(Socket, SocketAddr)> {
+    match connected {
+        Ok(connected) => Some(connected),
+        Err(e) => {
+            for (addr, e) in &e.attempts {
+                eprintln!("client: connect {} err: {}", addr, e);
             }
+            None
         }
-    };
-    println!("IP: {:?}", ip);
+    }
 }
 
 /// Section 6.2 "A Simple Stream Client"
-/// Bindings: `libc`
+/// Bindings: `libc`, through [`Socket`]
 ///
 /// Protocol: `TCP`
 ///
+/// With [`StreamClient::happy_eyeballs`], the addresses are raced with [`connect::happy_eyeballs`]
+/// instead of being tried one after the other, so an unreachable family doesn't stall us.
+///
+/// With [`StreamClient::connect_timeout`], each address gets that long with
+/// [`connect::connect_timeout`], instead of whatever the kernel decides.
+///
+/// Unlike the book, we read until the server closes the connection, and write the bytes
+/// as they come to stdout or [`StreamClient::output`].
+/// The messages about the connection go to stderr.
+///
 /// Original: [client.c](https://beej.us/guide/bgnet/examples/client.c)
-pub fn streamclient(host: String) {
-    let service = "3490";
+pub fn streamclient(config: StreamClient) -> Result<(), ResolveError> {
+    let StreamClient {
+        host,
+        port,
+        family,
+        max_data_size,
+        output,
+        max_bytes,
+        timeout,
+        connect_timeout,
+        happy_eyeballs,
+        attempt_delay,
+        resolver,
+        hosts_file,
+    } = config;
+    let service = port.to_string();
 
-    let hints = AddrInfo::builder()
-        .family(Family::Unspecified)
-        .socktype(SocketType::Stream)
-        .build();
+    let addrs = match resolver {
+        ResolverKind::System => system_lookup(&host, &service, family)?,
+        ResolverKind::Builtin => builtin_lookup(&host, &service, family, hosts_file.as_deref())?,
+    };
 
-    let mut servinfo = ptr::null_mut();
-    unsafe {
-        let node = CString::new(host).expect("Invalid host");
-        let c_node: *const libc::c_char = node.as_ptr() as *const libc::c_char;
-        let service = CString::new(service).expect("Invalid service");
-        let c_service: *const libc::c_char = service.as_ptr() as *const libc::c_char;
-        let hints = hints.into();
-        libc::getaddrinfo(c_node, c_service, &hints, &mut servinfo);
-    }
+    let connected = match (happy_eyeballs, connect_timeout) {
+        // race all the results, and keep the first one that connects
+        (true, _) => report_attempts(connect::happy_eyeballs(
+            addrs.iter().copied(),
+            attempt_delay,
+        )),
+        // one after the other, but don't wait on an address for longer than `timeout`
+        (false, Some(timeout)) => {
+            report_attempts(connect::connect_timeout(addrs.iter().copied(), timeout))
+        }
+        (false, None) => connect_sequential(&addrs),
+    };
 
-    let mut sockfd = mem::MaybeUninit::<libc::c_int>::uninit();
-    while !servinfo.is_null() {
-        unsafe {
-            let _sockfd = libc::socket(
-                (*servinfo).ai_family,
-                (*servinfo).ai_socktype,
-                (*servinfo).ai_protocol,
-            );
+    let Some((sockfd, addr)) = connected else {
+        eprintln!("client: failed to connect");
+        process::exit(2);
+    };
+    showaddrinfo(&addr);
+    if let Err(e) = sockfd.set_recv_timeout(timeout) {
+        eprintln!("client: setsockopt err: {}", e);
+        process::exit(1);
+    }
 
-            if _sockfd == -1 {
-                servinfo = (*servinfo).ai_next as *mut libc::addrinfo;
-                continue;
+    let mut out: Box<dyn Write> = match &output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                eprintln!("client: {}: {}", path.display(), e);
+                process::exit(1);
             }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
 
-            let errr = libc::connect(_sockfd, (*servinfo).ai_addr, (*servinfo).ai_addrlen);
-            if errr == -1 {
-                libc::close(_sockfd);
-                servinfo = (*servinfo).ai_next as *mut libc::addrinfo;
-                eprintln!("client: connect err");
-                continue;
+    // keep reading until the server is done, or we have enough
+    let mut buf = vec![0u8; max_data_size];
+    let mut received: u64 = 0;
+    loop {
+        let wanted = match max_bytes {
+            Some(max_bytes) => buf.len().min((max_bytes - received) as usize),
+            None => buf.len(),
+        };
+        if wanted == 0 {
+            break;
+        }
+        let numbytes = match sockfd.recv(&mut buf[..wanted]) {
+            Ok(0) => break,
+            Ok(numbytes) => numbytes,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                eprintln!("client: recv err: timed out after {} bytes", received);
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("client: recv err: {}", e);
+                process::exit(1);
             }
-            sockfd.write(_sockfd);
+        };
+        // flushing right away, so slow services show up as they come
+        if let Err(e) = out.write_all(&buf[..numbytes]).and_then(|()| out.flush()) {
+            eprintln!("client: write err: {}", e);
+            process::exit(1);
         }
-        break;
-    }
-
-    if servinfo.is_null() {
-        eprintln!("client: failed to connect");
-        unsafe { libc::exit(2) };
-    }
-    showaddrinfo(unsafe { servinfo.as_ref().unwrap() });
-    const MAXDATASIZE: usize = 100;
-    let mut buf = [0u8; MAXDATASIZE];
-    let numbytes = unsafe {
-        libc::recv(
-            sockfd.assume_init(),
-            buf.as_mut_ptr() as *mut libc::c_void,
-            MAXDATASIZE - 1,
-            0,
-        )
-    };
-    if numbytes == -1 {
-        eprintln!("client: recv err");
-        unsafe { libc::exit(1) };
+        received += numbytes as u64;
     }
-    println!("client: received '{}'", unsafe {
-        CStr::from_ptr(buf.as_ptr() as *const libc::c_char)
-            .to_str()
-            .unwrap()
-    });
+    eprintln!("client: received {} bytes", received);
+    Ok(())
 }
@@ -0,0 +1,156 @@
+use std::{
+    io,
+    net::{IpAddr, SocketAddr, SocketAddrV6, UdpSocket},
+    process,
+    time::{Duration, Instant},
+};
+
+use nix::sys::socket::{self, sockopt, AddressFamily, SockFlag, SockType};
+
+use crate::{
+    discovery::{self, Announcement, MDNS_IPV4, MDNS_IPV6},
+    dns::Message,
+    interfaces, multicast,
+    types::Family,
+};
+
+/// Service discovery for the chat servers, beyond section 7.7 "Broadcast Packets—Hello, World!"
+///
+/// Bindings: `nix`, `std` once the socket is ready
+///
+/// Protocol: `UDP`, with DNS messages like multicast DNS
+///
+/// We send the query of [`discovery::query`] to the mDNS group of `family`, or with `broadcast`
+/// to the broadcast address of every interface, like `broadcaster --all-interfaces`.
+/// The servers started with `--announce` answer us in unicast, and we list the answers
+/// that arrived within `timeout`, with the address they came from.
+///
+/// ```console
+/// beej-rs chat-server --announce lab1
+/// beej-rs discover
+/// ```
+pub fn discover(
+    family: Family,
+    port: u16,
+    interface: Option<String>,
+    broadcast: bool,
+    timeout: Duration,
+) {
+    if broadcast && family == Family::Ipv6 {
+        eprintln!("discover: IPv6 has no broadcast, use its mDNS group");
+        process::exit(2);
+    }
+    let group = match family {
+        Family::Ipv6 => IpAddr::V6(MDNS_IPV6),
+        Family::Ipv4 | Family::Unspecified => IpAddr::V4(MDNS_IPV4),
+    };
+    let address_family = match group {
+        IpAddr::V4(_) => AddressFamily::Inet,
+        IpAddr::V6(_) => AddressFamily::Inet6,
+    };
+    let sockfd = socket::socket(address_family, SockType::Datagram, SockFlag::empty(), None)
+        .expect("Failed to create socket");
+
+    let targets: Vec<SocketAddr> = if broadcast {
+        socket::setsockopt(&sockfd, sockopt::Broadcast, &true)
+            .expect("Failed to set socket options");
+        let interfaces = interfaces::ipv4_interfaces().unwrap_or_else(|e| {
+            eprintln!("discover: getifaddrs: {}", e);
+            process::exit(1);
+        });
+        interfaces
+            .iter()
+            .filter(|i| i.can_broadcast)
+            .filter(|i| interface.as_ref().is_none_or(|name| &i.name == name))
+            .map(|i| SocketAddr::new(IpAddr::V4(i.broadcast()), port))
+            .collect()
+    } else {
+        if let Err(e) = multicast::set_send_options(&sockfd, group, 255, true, interface.as_deref())
+        {
+            eprintln!("discover: can't send to {}: {}", group, e);
+            process::exit(2);
+        }
+        let dest = match group {
+            IpAddr::V6(group) => {
+                let scope_id = interface
+                    .as_deref()
+                    .map(|name| multicast::interface_index(name).expect("if_nametoindex failed"))
+                    .unwrap_or(0);
+                SocketAddr::V6(SocketAddrV6::new(group, port, 0, scope_id))
+            }
+            IpAddr::V4(_) => SocketAddr::new(group, port),
+        };
+        vec![dest]
+    };
+    if targets.is_empty() {
+        eprintln!("discover: no interface with a broadcast address");
+        process::exit(2);
+    }
+
+    // the answers echo the id, ours are the ones we care about
+    let id = process::id() as u16;
+    let query = discovery::query(id).to_bytes().expect("query is valid");
+    let socket = UdpSocket::from(sockfd);
+    for target in &targets {
+        eprintln!("discover: asking {}", target);
+        if let Err(e) = socket.send_to(&query, target) {
+            eprintln!("discover: sendto {}: {}", target, e);
+            process::exit(1);
+        }
+    }
+
+    // gather until the deadline, whoever answers late is not listed
+    let deadline = Instant::now() + timeout;
+    let mut found: Vec<(Announcement, SocketAddr)> = Vec::new();
+    let mut buf = [0u8; 9000];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket
+            .set_read_timeout(Some(remaining))
+            .expect("Failed to set socket options");
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => panic!("recvfrom failed: {}", e),
+        };
+        let Ok(message) = Message::parse(&buf[..len]) else {
+            continue;
+        };
+        if message.header.id != id {
+            continue;
+        }
+        for announcement in discovery::announcements(&message) {
+            let addr = SocketAddr::new(from.ip(), announcement.port);
+            let addr = match addr {
+                // keep the interface of link-local addresses
+                SocketAddr::V6(v6) => match from {
+                    SocketAddr::V6(from) => {
+                        SocketAddr::V6(SocketAddrV6::new(*v6.ip(), v6.port(), 0, from.scope_id()))
+                    }
+                    SocketAddr::V4(_) => addr,
+                },
+                SocketAddr::V4(_) => addr,
+            };
+            if !found
+                .iter()
+                .any(|(a, at)| a.name == announcement.name && *at == addr)
+            {
+                found.push((announcement, addr));
+            }
+        }
+    }
+
+    if found.is_empty() {
+        eprintln!("discover: no server answered within {:?}", timeout);
+        process::exit(1);
+    }
+    found.sort_by(|(a, a_addr), (b, b_addr)| (&a.name, a_addr).cmp(&(&b.name, b_addr)));
+    for (announcement, addr) in found {
+        println!("{}\t{}\t{}", announcement.name, addr, announcement.family);
+    }
+}
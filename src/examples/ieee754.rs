@@ -0,0 +1,54 @@
+use std::process;
+
+use crate::byteorder::{self, htonll};
+
+/// Section 7.5 "Serialization—How to Pack Data"
+///
+/// Encode floats with the portable `pack754`, and decode them back
+///
+/// Bindings: none, it's all arithmetic
+///
+/// Prints what goes on the wire, in network byte order thanks to [`htonll`].
+/// With `check`, the encoding must match [`f64::to_be_bytes`], otherwise we exit with `1`.
+///
+/// Original: [ieee754.c](https://beej.us/guide/bgnet/examples/ieee754.c)
+pub fn ieee754(values: Vec<f64>, bits: u32, expbits: u32, check: bool) {
+    if bits <= expbits + 1 || bits - expbits - 1 > 52 {
+        eprintln!("ieee754: the significand needs 1 to 52 bits");
+        process::exit(2);
+    }
+    if check && (bits, expbits) != (64, 11) {
+        eprintln!("ieee754: --check compares with f64, it needs --bits 64 --expbits 11");
+        process::exit(2);
+    }
+
+    let mut mismatches = 0;
+    for f in values {
+        let encoded = byteorder::pack754(f, bits, expbits);
+        let after = byteorder::unpack754(encoded, bits, expbits);
+        // the encoded float is in the low bytes, so it's at the end once big endian
+        let wire = htonll(encoded).to_ne_bytes();
+        let wire: Vec<String> = wire[(8 - bits.div_ceil(8) as usize)..]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        println!("float before : {:?}", f);
+        println!("float encoded: {:#x}", encoded);
+        println!("on the wire  : {}", wire.join(" "));
+        println!("float after  : {:?}", after);
+        if check {
+            match byteorder::pack754_checked(f) {
+                Ok(_) => println!("to_be_bytes  : same"),
+                Err(e) => {
+                    println!("to_be_bytes  : {}", e);
+                    mismatches += 1;
+                }
+            }
+        }
+        println!();
+    }
+    if mismatches > 0 {
+        process::exit(1);
+    }
+}
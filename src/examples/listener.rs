@@ -3,7 +3,11 @@ use std::{
     os::fd::AsRawFd,
 };
 
-use crate::types::Family;
+use crate::{
+    serialize,
+    shutdown::{Event, Shutdown},
+    types::Family,
+};
 
 /// Section 6.3 "Datagram Sockets"
 ///
@@ -14,8 +18,15 @@ use crate::types::Family;
 /// This is a UDP server listening to UDP messages.
 /// Because UDP is connectionless and fires packets off, we are explicit about the family (ipv4 or ipv6)
 ///
+/// With `unpack`, the datagrams are decoded with [`serialize::unpack`] instead of printed as text.
+///
+/// `SIGINT` or `SIGTERM` stops listening.
+///
 /// Original: [listener.c](https://beej.us/guide/bgnet/examples/listener.c)
-pub fn socketlistener(port: u16, family: Family) {
+pub fn socketlistener(port: u16, family: Family, unpack: Option<String>) {
+    // Readable once we are asked to stop
+    let shutdown = Shutdown::install().expect("Failed to install signal handlers");
+
     let local_addr = match family {
         Family::Ipv4 => SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)),
         Family::Ipv6 | Family::Unspecified => {
@@ -76,7 +87,7 @@ pub fn socketlistener(port: u16, family: Family) {
 
     // This loop is not in the book, I just added it to avoid
     // having to run the program multiple times
-    loop {
+    while shutdown.wait(&sockfd).expect("poll failed") == Event::Readable {
         let mut buf = [0u8; 1024];
         let (len, _addr) = nix::sys::socket::recvfrom::<nix::sys::socket::SockaddrStorage>(
             sockfd.as_raw_fd(),
@@ -84,7 +95,19 @@ pub fn socketlistener(port: u16, family: Family) {
         )
         .expect("recvfrom failed");
         // println!("Received {} bytes from {:?}", len, addr);
-        let msg = std::str::from_utf8(&buf[..len]).expect("Failed to convert to string");
+        if let Some(format) = &unpack {
+            // someone else might be talking to us, that's no reason to stop
+            match serialize::unpack(format, &buf[..len]) {
+                Ok(values) => {
+                    let values: Vec<String> = values.iter().map(ToString::to_string).collect();
+                    println!("{}", values.join(" "));
+                }
+                Err(e) => eprintln!("listener: bad datagram: {}", e),
+            }
+            continue;
+        }
+        // anyone can send us bytes that aren't text
+        let msg = String::from_utf8_lossy(&buf[..len]);
         println!("{}", msg);
     }
 }
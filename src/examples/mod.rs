@@ -1,6 +1,9 @@
 mod showip;
 pub use showip::showip;
 
+mod reverse;
+pub use reverse::reverse;
+
 mod server;
 pub use server::streamserver;
 
@@ -16,14 +19,29 @@ pub use talker::sockettalker;
 mod poll;
 pub use poll::pollstdin;
 
-mod pollserver;
-pub use pollserver::pollserver;
+mod chatserver;
+pub use chatserver::{chatserver, goodbye_message};
+
+mod chatclient;
+pub use chatclient::chatclient;
 
 mod select;
 pub use select::select;
 
-mod selectserver;
-pub use selectserver::select_server;
+mod ieee754;
+pub use ieee754::ieee754;
 
 mod broadcaster;
 pub use broadcaster::broadcaster;
+
+mod broadcastlistener;
+pub use broadcastlistener::broadcastlistener;
+
+mod multicastsend;
+pub use multicastsend::multicast_send;
+
+mod multicastrecv;
+pub use multicastrecv::multicast_recv;
+
+mod discover;
+pub use discover::discover;
@@ -0,0 +1,67 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::fd::AsRawFd,
+    process,
+};
+
+use nix::sys::socket::{self, sockopt, AddressFamily, SockFlag, SockType, SockaddrStorage};
+
+use crate::{
+    multicast,
+    shutdown::{Event, Shutdown},
+};
+
+/// Multicast receiver, beyond section 7.7 "Broadcast Packets—Hello, World!"
+///
+/// Bindings: `nix`
+///
+/// Protocol: `UDP`
+///
+/// Like [`broadcastlistener`](super::broadcastlistener), we bind to the wildcard address
+/// with `SO_REUSEADDR`, but nothing arrives until we join the `group`:
+/// with `IP_ADD_MEMBERSHIP` or `IPV6_ADD_MEMBERSHIP`, on `interface` if given.
+/// With a `source`, the join is source-specific (`MCAST_JOIN_SOURCE_GROUP`) and the other
+/// senders to the group are filtered out by the kernel.
+///
+/// `SIGINT` or `SIGTERM` stops listening, leaving the group when the socket is closed.
+///
+/// ```console
+/// beej-rs multicast-recv 239.255.0.1
+/// beej-rs multicast-send 239.255.0.1 "Hello, World!"
+/// ```
+pub fn multicast_recv(group: IpAddr, port: u16, interface: Option<String>, source: Option<IpAddr>) {
+    // Readable once we are asked to stop
+    let shutdown = Shutdown::install().expect("Failed to install signal handlers");
+
+    let (family, local_addr) = match group {
+        IpAddr::V4(_) => (AddressFamily::Inet, IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        IpAddr::V6(_) => (AddressFamily::Inet6, IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+    };
+    let sockfd = socket::socket(family, SockType::Datagram, SockFlag::empty(), None)
+        .expect("Failed to create socket");
+    // other receivers of the group on this host need the port too
+    socket::setsockopt(&sockfd, sockopt::ReuseAddr, &true).expect("Failed to set SO_REUSEADDR");
+    let local_addr = SockaddrStorage::from(SocketAddr::new(local_addr, port));
+    socket::bind(sockfd.as_raw_fd(), &local_addr).expect("Failed to bind to socket");
+
+    if let Err(e) = multicast::join(&sockfd, group, interface.as_deref(), source) {
+        eprintln!("multicast-recv: can't join {}: {}", group, e);
+        process::exit(2);
+    }
+    match source {
+        Some(source) => println!("Joined {} from {} on port {}", group, source, port),
+        None => println!("Joined {} on port {}", group, port),
+    }
+
+    while shutdown.wait(&sockfd).expect("poll failed") == Event::Readable {
+        let mut buf = [0u8; 1024];
+        let (len, addr) = socket::recvfrom::<SockaddrStorage>(sockfd.as_raw_fd(), &mut buf)
+            .expect("recvfrom failed");
+        let from = addr.map(|a| a.to_string()).unwrap_or_default();
+        println!(
+            "got packet from {}: {:?}",
+            from,
+            String::from_utf8_lossy(&buf[..len])
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    net::{IpAddr, SocketAddr, SocketAddrV6},
+    os::fd::AsRawFd,
+    process,
+};
+
+use nix::sys::socket::{self, AddressFamily, MsgFlags, SockFlag, SockType, SockaddrStorage};
+
+use crate::multicast;
+
+/// Multicast sender, beyond section 7.7 "Broadcast Packets—Hello, World!"
+///
+/// Bindings: `nix`
+///
+/// Protocol: `UDP`
+///
+/// Sending needs no membership, `sendto` the group is enough. The options say where it goes:
+/// `hops` is `IP_MULTICAST_TTL` or `IPV6_MULTICAST_HOPS`, `1` keeps it on the subnet;
+/// `loopback` is `IP_MULTICAST_LOOP` or `IPV6_MULTICAST_LOOP`, for the receivers on this host;
+/// and `interface` picks the way out with `IP_MULTICAST_IF` or `IPV6_MULTICAST_IF`.
+/// It's also the scope of link-local IPv6 groups like `ff02::1`.
+///
+/// Sent on the loopback interface, the datagram comes back even without `loopback`:
+/// that's the interface doing its job. Linux only routes IPv4 multicast there, not IPv6.
+///
+/// ```console
+/// beej-rs multicast-recv ff02::1234 --interface eth0
+/// beej-rs multicast-send ff02::1234 --interface eth0 "Hello, World!"
+/// ```
+pub fn multicast_send(
+    group: IpAddr,
+    port: u16,
+    message: String,
+    hops: u8,
+    loopback: bool,
+    interface: Option<String>,
+) {
+    let family = match group {
+        IpAddr::V4(_) => AddressFamily::Inet,
+        IpAddr::V6(_) => AddressFamily::Inet6,
+    };
+    let sockfd = socket::socket(family, SockType::Datagram, SockFlag::empty(), None)
+        .expect("Failed to create socket");
+    if let Err(e) =
+        multicast::set_send_options(&sockfd, group, hops, loopback, interface.as_deref())
+    {
+        eprintln!("multicast-send: can't send to {}: {}", group, e);
+        process::exit(2);
+    }
+
+    let dest = match group {
+        IpAddr::V4(_) => SocketAddr::new(group, port),
+        IpAddr::V6(group) => {
+            // set_send_options already checked the interface
+            let scope_id = interface
+                .as_deref()
+                .map(|name| multicast::interface_index(name).expect("if_nametoindex failed"))
+                .unwrap_or(0);
+            SocketAddr::V6(SocketAddrV6::new(group, port, 0, scope_id))
+        }
+    };
+    println!("Sending message to {}", dest);
+    // ENETUNREACH when no route takes the group anywhere, like IPv6 on the loopback
+    if let Err(e) = socket::sendto(
+        sockfd.as_raw_fd(),
+        message.as_bytes(),
+        &SockaddrStorage::from(dest),
+        MsgFlags::empty(),
+    ) {
+        eprintln!("multicast-send: sendto {}: {}", dest, e);
+        process::exit(1);
+    }
+}
@@ -9,6 +9,8 @@ use std::{io::stdin, os::fd::AsFd};
 /// When polling, the process is put to sleep until the FD is ready.
 ///
 /// Original: [poll.c](https://beej.us/guide/bgnet/examples/poll.c)
+// kept as the book writes it
+#[allow(clippy::needless_return, clippy::unnecessary_cast)]
 pub fn pollstdin() {
     let stfd = stdin();
     let pfd = nix::poll::PollFd::new(stfd.as_fd(), nix::poll::PollFlags::POLLIN);
@@ -0,0 +1,25 @@
+use std::net::{IpAddr, SocketAddr};
+
+use socket2::SockAddr;
+
+use crate::{
+    resolver::{self, ResolveError},
+    types::NameInfoFlags,
+};
+
+/// Section 9.8 "getnameinfo()"
+///
+/// Look up the host and service names for an IP address and port.
+/// `getnameinfo` is the modern replacement for `gethostbyaddr` and `getservbyport`,
+/// and works with both IPv4 and IPv6.
+pub fn reverse(ip: IpAddr, port: Option<u16>, flags: NameInfoFlags) -> Result<(), ResolveError> {
+    let addr = SockAddr::from(SocketAddr::new(ip, port.unwrap_or(0)));
+    let nameinfo = resolver::getnameinfo(&addr, flags)?;
+
+    println!("Names for '{}'\n", ip);
+    println!("\tHost: {}", nameinfo.host);
+    if port.is_some() {
+        println!("\tService: {}", nameinfo.service);
+    }
+    Ok(())
+}
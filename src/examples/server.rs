@@ -1,154 +1,249 @@
 use std::{
-    ffi::{CStr, CString},
-    mem, ptr,
+    process, ptr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, SyncSender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
 };
 
-use socket2::SockAddr;
+use nix::{
+    errno::Errno,
+    sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal},
+    unistd::{self, ForkResult},
+};
 
 use crate::{
-    builders::AddrInfo,
-    types::{Family, Flag, SockFd},
+    builders::{AddrInfo, StreamServer},
+    partial,
+    resolver::{self, ResolveError},
+    shutdown::{Event, Shutdown},
+    socket::Socket,
+    types::{Flag, ServerMode},
 };
 
-/// Section 6.1 "A Simple Stream Server"
+extern "C" fn sigchld_handler(_: libc::c_int) {
+    // waitpid() might overwrite errno, so we save and restore it
+    let saved_errno = Errno::last_raw();
+    while unsafe { libc::waitpid(-1, ptr::null_mut(), libc::WNOHANG) } > 0 {}
+    Errno::set_raw(saved_errno);
+}
+
+/// Start `workers` threads serving the connections sent on the queue.
 ///
-/// Contains unsafe code
+/// The queue holds as many connections as there are workers,
+/// once it's full sending blocks until a worker is free.
+fn start_pool(
+    workers: usize,
+    banner: &Arc<[u8]>,
+    served: &Arc<AtomicUsize>,
+) -> (SyncSender<Socket>, Vec<JoinHandle<()>>) {
+    let (sender, receiver) = mpsc::sync_channel::<Socket>(workers);
+    let receiver = Arc::new(Mutex::new(receiver));
+    let workers = (0..workers)
+        .map(|_| {
+            let receiver = Arc::clone(&receiver);
+            let banner = Arc::clone(banner);
+            let served = Arc::clone(served);
+            thread::spawn(move || loop {
+                // the lock is released before serving, so the others can take the next one
+                let next = receiver.lock().expect("a worker panicked").recv();
+                match next {
+                    Ok(new_fd) => {
+                        hello(new_fd, &banner);
+                        served.fetch_add(1, Ordering::Relaxed);
+                    }
+                    // the queue is closed, we are shutting down
+                    Err(_) => break,
+                }
+            })
+        })
+        .collect();
+    (sender, workers)
+}
+
+fn hello(new_fd: Socket, banner: &[u8]) {
+    if let Err(e) = partial::sendall(&new_fd, banner) {
+        eprintln!("server: send err: {}", e);
+    }
+    // new_fd is closed here
+}
+
+/// Section 6.1 "A Simple Stream Server"
 ///
-/// Bindings: `libc`
+/// Bindings: `libc`, through [`Socket`]
 ///
 /// Protocol: `TCP`
 ///
+/// With [`ServerMode::Fork`], each connection is served by a child process,
+/// and a `SIGCHLD` handler reaps the children when they are done.
+/// [`ServerMode::Thread`] spawns a thread per connection instead, and
+/// [`ServerMode::Pool`] hands them to a fixed number of threads.
+///
+/// `SIGINT` or `SIGTERM` stops the server: it finishes the connections it accepted,
+/// prints how many it served, and returns.
+///
 /// Original: [server.c](https://beej.us/guide/bgnet/examples/server.c)
-pub fn streamserver() {
-    let family = Family::Unspecified;
-    let host = "localhost";
-    let service = "3490";
-
-    // let addrinfo = unsafe { buildaddrinfo(Family::Unspecified) };
-    let addrinfo = AddrInfo::builder()
+pub fn streamserver(config: StreamServer) -> Result<(), ResolveError> {
+    let StreamServer {
+        host,
+        port,
+        family,
+        backlog,
+        banner,
+        mode,
+        workers,
+    } = config;
+    let service = port.to_string();
+    // shared with the threads
+    let banner: Arc<[u8]> = banner.into();
+
+    let hints = AddrInfo::builder()
         .family(family)
         .flags(Flag::Passive)
         .build();
-    let hints: libc::addrinfo = addrinfo.into();
-
-    let mut servinfo = ptr::null_mut();
-    let rv = unsafe {
-        let node = CString::new(host).expect("Invalid node");
-        let c_node: *const libc::c_char = node.as_ptr() as *const libc::c_char;
-        let port = CString::new(service).expect("Invalid port");
-        let c_port: *const libc::c_char = port.as_ptr() as *const libc::c_char;
-        // let c_port = port.as_ptr();
-        println!("Starting server in {host}:{service}");
-        libc::getaddrinfo(c_node, c_port, &hints, &mut servinfo)
-    };
 
-    if rv != 0 {
-        eprintln!("getaddrinfo: {}", unsafe {
-            CStr::from_ptr(libc::gai_strerror(rv)).to_str().unwrap()
-        });
-        return;
-    }
+    println!("Starting server in {host}:{service}");
+    let servinfo = resolver::getaddrinfo(Some(&host), Some(&service), hints)?;
 
-    let mut sockfd = SockFd::Empty;
+    let mut sockfd = None;
     // loop through all the results and bind to the first we can
-    while !servinfo.is_null() {
-        unsafe {
-            let _sockfd = libc::socket(
-                (*servinfo).ai_family,
-                (*servinfo).ai_socktype,
-                (*servinfo).ai_protocol,
-            );
-            if _sockfd == -1 {
-                eprintln!("server: socket err");
-                servinfo = (*servinfo).ai_next as *mut libc::addrinfo;
+    for entry in &servinfo {
+        let (ai_family, ai_socktype, ai_protocol) = entry.socket_args();
+        let socket = match Socket::from_args(ai_family, ai_socktype, ai_protocol) {
+            Ok(socket) => socket,
+            Err(e) => {
+                eprintln!("server: socket err: {}", e);
                 continue;
             }
-            let optval_yes: libc::c_int = 1;
-            let errr = libc::setsockopt(
-                _sockfd,
-                libc::SOL_SOCKET,
-                libc::SO_REUSEADDR,
-                &optval_yes as *const _ as *const libc::c_void,
-                mem::size_of_val(&optval_yes) as libc::socklen_t,
-            );
-
-            if errr == -1 {
-                eprintln!("server: setsockopt err");
-                libc::exit(1);
-            }
-            let errr = libc::bind(
-                _sockfd,
-                (*servinfo).ai_addr,
-                (*servinfo).ai_addrlen as libc::socklen_t,
-            );
-            if errr == -1 {
-                libc::close(_sockfd);
-                eprintln!("server: bind err");
-                servinfo = (*servinfo).ai_next as *mut libc::addrinfo;
-                continue;
-            }
-            sockfd = SockFd::Initialized(_sockfd);
+        };
+        if let Err(e) = socket.set_reuseaddr(true) {
+            eprintln!("server: setsockopt err: {}", e);
+            process::exit(1);
         }
+        // the socket is closed when dropped, so no need to clean up on failure
+        if let Err(e) = socket.bind(entry.sockaddr()) {
+            eprintln!("server: bind err: {}", e);
+            continue;
+        }
+        sockfd = Some(socket);
         break;
     }
+    // all done with this structure, the list is freed here
+    drop(servinfo);
 
-    if servinfo.is_null() {
+    let Some(sockfd) = sockfd else {
         eprintln!("server: failed to bind socket");
-        unsafe { libc::exit(1) };
+        process::exit(1);
+    };
+    if let Err(e) = sockfd.listen(backlog) {
+        eprintln!("server: listen err: {}", e);
+        process::exit(1);
     }
 
-    if sockfd == SockFd::Empty {
-        eprintln!("server: failed to create socket");
-        unsafe { libc::exit(1) };
+    if mode == ServerMode::Fork {
+        // reap all dead processes
+        let sa = SigAction::new(
+            SigHandler::Handler(sigchld_handler),
+            SaFlags::SA_RESTART,
+            SigSet::empty(),
+        );
+        if let Err(e) = unsafe { signal::sigaction(Signal::SIGCHLD, &sa) } {
+            eprintln!("server: sigaction err: {}", e);
+            process::exit(1);
+        }
     }
-    let sockfd = sockfd.into();
-    let errr = unsafe {
-        // how many pending connections queue will hold
-        let backlog = 10;
-        libc::listen(sockfd, backlog)
+    let shutdown = match Shutdown::install() {
+        Ok(shutdown) => shutdown,
+        Err(e) => {
+            eprintln!("server: sigaction err: {}", e);
+            process::exit(1);
+        }
     };
 
-    if errr == -1 {
-        eprintln!("server: listen err");
-        unsafe { libc::exit(1) };
-    }
+    let served = Arc::new(AtomicUsize::new(0));
+    let mut threads = Vec::new();
+    let pool = (mode == ServerMode::Pool).then(|| start_pool(workers, &banner, &served));
 
     println!("server: waiting for connections...");
 
     loop {
-        let mut their_addr = mem::MaybeUninit::<libc::sockaddr_storage>::uninit();
-        let mut sin_size = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
-        let new_fd = unsafe {
-            libc::accept(
-                sockfd,
-                their_addr.as_mut_ptr() as *mut libc::sockaddr,
-                &mut sin_size,
-            )
-        };
-
-        if new_fd == -1 {
-            eprintln!("server: accept err");
-            continue;
+        match shutdown.wait(&sockfd) {
+            Ok(Event::Readable) => {}
+            Ok(Event::Shutdown) => break,
+            Err(e) => {
+                eprintln!("server: poll err: {}", e);
+                process::exit(1);
+            }
         }
-
-        let s = unsafe { SockAddr::new(their_addr.assume_init(), sin_size) };
-        println!("server: got connection from {:?}", s);
-
-        let msg = CString::new("Hello, world!").expect("Invalid message");
-        let len = msg.as_bytes().len();
-        let errr = unsafe {
-            libc::send(
-                new_fd,
-                msg.as_ptr() as *const libc::c_void,
-                len,
-                libc::MSG_NOSIGNAL,
-            )
+        let (new_fd, their_addr) = match sockfd.accept() {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("server: accept err: {}", e);
+                continue;
+            }
         };
+        println!("server: got connection from {:?}", their_addr);
 
-        if errr == -1 {
-            eprintln!("server: send err");
+        match mode {
+            ServerMode::Iterative => {
+                hello(new_fd, &banner);
+                served.fetch_add(1, Ordering::Relaxed);
+            }
+            // SAFETY: we are single threaded, the child can do anything it wants
+            ServerMode::Fork => match unsafe { unistd::fork() } {
+                Ok(ForkResult::Child) => {
+                    // child doesn't need the listener
+                    drop(sockfd);
+                    hello(new_fd, &banner);
+                    process::exit(0);
+                }
+                // parent doesn't need this, it's closed when dropped
+                Ok(ForkResult::Parent { .. }) => {
+                    drop(new_fd);
+                    served.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => eprintln!("server: fork err: {}", e),
+            },
+            ServerMode::Thread => {
+                // forget about the threads that are done
+                threads.retain(|thread: &JoinHandle<()>| !thread.is_finished());
+                let banner = Arc::clone(&banner);
+                let served = Arc::clone(&served);
+                threads.push(thread::spawn(move || {
+                    hello(new_fd, &banner);
+                    served.fetch_add(1, Ordering::Relaxed);
+                }));
+            }
+            ServerMode::Pool => {
+                if let Some((sender, _)) = &pool {
+                    // blocks while the queue is full, so we stop accepting
+                    if sender.send(new_fd).is_err() {
+                        eprintln!("server: all workers are gone");
+                        break;
+                    }
+                }
+            }
         }
+    }
 
-        unsafe { libc::close(new_fd) };
+    println!("server: shutting down...");
+    drop(sockfd);
+    // let the connections in flight finish
+    for thread in threads {
+        let _ = thread.join();
+    }
+    if let Some((sender, workers)) = pool {
+        // workers stop once the queue is closed and empty
+        drop(sender);
+        for worker in workers {
+            let _ = worker.join();
+        }
     }
+    println!(
+        "server: served {} connections",
+        served.load(Ordering::Relaxed)
+    );
+    Ok(())
 }
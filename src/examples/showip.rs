@@ -1,53 +1,186 @@
-use std::{ffi::CString, ptr};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 
-use socket2::SockAddr;
+use serde::Serialize;
 
-use crate::{builders::AddrInfo, types::Family};
+use crate::{
+    builders::AddrInfo,
+    dns,
+    resolver::{self, AddrInfoEntry, ResolveError},
+    types::{Family, Flags, OutputFormat, Protocol, ResolverKind, SocketType},
+};
 
-/// Section 5.1 "getaddrinfo() -- Prepare to Launch"
-pub fn showip(host: String, family: Family, service: String) {
-    println!("IP addresses for '{}'\n", host);
-
-    let host = CString::new(host).expect("Invalid host");
-    let c_host: *const libc::c_char = host.as_ptr() as *const libc::c_char;
-
-    let service =
-        CString::new(service).expect("Invalid service, service should map to a port number");
-    let service: *const libc::c_char = service.as_ptr() as *const libc::c_char;
-    let addrino = AddrInfo::builder().family(family).build();
-
-    let hints = addrino.into();
-
-    let mut res = ptr::null_mut();
-    unsafe { libc::getaddrinfo(c_host, service, &hints, &mut res) };
-
-    while !res.is_null() {
-        let ((), sockaddr) = unsafe {
-            SockAddr::try_init(|storage, len| {
-                *len = (*res).ai_addrlen as _;
-                std::ptr::copy_nonoverlapping(
-                    (*res).ai_addr as *const u8,
-                    storage as *mut u8,
-                    (*res).ai_addrlen as usize,
-                );
-                Ok(())
-            })
+/// One line of the structured output.
+///
+/// The field names and their order are the schema of the `json` and `csv` formats,
+/// so keep them stable.
+#[derive(Debug, Serialize)]
+struct Record {
+    #[serde(skip)]
+    socket_addr: SocketAddr,
+    address: String,
+    port: u16,
+    family: String,
+    socktype: String,
+    protocol: String,
+    canonname: Option<String>,
+}
+
+impl Record {
+    const CSV_HEADER: &'static str = "address,port,family,socktype,protocol,canonname";
+
+    fn new(
+        socket_addr: SocketAddr,
+        socktype: String,
+        protocol: String,
+        canonname: Option<String>,
+    ) -> Self {
+        let family = match socket_addr {
+            SocketAddr::V4(_) => Family::Ipv4,
+            SocketAddr::V6(_) => Family::Ipv6,
+        };
+        Record {
+            socket_addr,
+            address: socket_addr.ip().to_string(),
+            port: socket_addr.port(),
+            family: family.to_string(),
+            socktype,
+            protocol,
+            canonname,
         }
-        .expect("to create a socket");
+    }
+
+    fn csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            self.address,
+            self.port,
+            self.family,
+            self.socktype,
+            self.protocol,
+            csv_escape(self.canonname.as_deref().unwrap_or_default())
+        )
+    }
 
-        res = (unsafe { *res }).ai_next as *mut libc::addrinfo;
-        println!("\t{}", sockaddr.as_socket().expect("failed to extract IP"));
+    fn text(&self) {
+        if let Some(canonname) = &self.canonname {
+            println!("\tCanonical name: {}", canonname);
+        }
+        println!("\t{}", self.socket_addr);
 
-        match sockaddr.family() as i32 {
-            libc::AF_INET => {
+        match self.socket_addr {
+            SocketAddr::V4(_) => {
                 println!("\tFamily: IPv4");
             }
-            libc::AF_INET6 => {
+            SocketAddr::V6(_) => {
                 println!("\tFamily: IPv6");
             }
-            _ => {
-                println!("\tUnknown family");
+        }
+    }
+}
+
+impl From<&AddrInfoEntry> for Record {
+    fn from(entry: &AddrInfoEntry) -> Self {
+        let (_, socktype, protocol) = entry.socket_args();
+        // a type we don't know about is still worth showing
+        let socktype = entry
+            .socktype()
+            .map_or_else(|_| socktype.to_string(), |socktype| socktype.to_string());
+        let protocol = entry
+            .protocol()
+            .map_or_else(|_| protocol.to_string(), |protocol| protocol.to_string());
+        Record::new(
+            entry.socket_addr().expect("failed to extract IP"),
+            socktype,
+            protocol,
+            entry.canonname().map(String::from),
+        )
+    }
+}
+
+// Quote a field only when needed, doubling the quotes inside
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn system_lookup(host: &str, service: &str, hints: AddrInfo) -> Result<Vec<Record>, ResolveError> {
+    let res = resolver::getaddrinfo(Some(host), Some(service), hints)?;
+    Ok(res.iter().map(|entry| Record::from(&entry)).collect())
+}
+
+// Same shape as getaddrinfo: canonical name on the first result only.
+// Only TCP and UDP have services, like getaddrinfo we refuse the other socket types.
+fn builtin_lookup(
+    host: &str,
+    service: &str,
+    hints: AddrInfo,
+    hosts_file: Option<&Path>,
+) -> Result<Vec<Record>, ResolveError> {
+    let socktype = hints.socktype().unwrap_or(SocketType::Stream);
+    let protocol = match socktype {
+        SocketType::Stream => Protocol::Tcp,
+        SocketType::Datagram => Protocol::Udp,
+        _ => return Err(ResolveError::SockType),
+    };
+    let port = resolver::getservbyname(service, socktype)?;
+    let lookup = dns::ResolverChain::from_system(hosts_file).lookup_ip(host, hints.family())?;
+
+    let mut canonname = hints
+        .flags()
+        .contains(Flags::CANONNAME)
+        .then(|| lookup.canonname.clone());
+    Ok(lookup
+        .with_port(port)
+        .into_iter()
+        .map(|addr| {
+            Record::new(
+                addr,
+                socktype.to_string(),
+                protocol.to_string(),
+                canonname.take(),
+            )
+        })
+        .collect())
+}
+
+/// Section 5.1 "getaddrinfo() -- Prepare to Launch"
+///
+/// With [`ResolverKind::Builtin`], names are resolved by our own [`dns`] module
+/// instead of glibc: first with `hosts_file`, then with the nameservers.
+pub fn showip(
+    host: String,
+    service: String,
+    hints: AddrInfo,
+    format: OutputFormat,
+    resolver: ResolverKind,
+    hosts_file: Option<PathBuf>,
+) -> Result<(), ResolveError> {
+    let records = match resolver {
+        ResolverKind::System => system_lookup(&host, &service, hints)?,
+        ResolverKind::Builtin => builtin_lookup(&host, &service, hints, hosts_file.as_deref())?,
+    };
+
+    match format {
+        OutputFormat::Text => {
+            println!("IP addresses for '{}'\n", host);
+            records.iter().for_each(Record::text);
+        }
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&records).expect("records are valid json");
+            println!("{}", json);
+        }
+        OutputFormat::Csv => {
+            println!("{}", Record::CSV_HEADER);
+            for record in &records {
+                println!("{}", record.csv());
             }
         }
     }
+    Ok(())
 }
@@ -1,8 +1,11 @@
 use std::{
     net::{IpAddr, SocketAddrV4, SocketAddrV6},
     os::fd::AsRawFd,
+    process,
 };
 
+use crate::serialize;
+
 /// Section 6.3 "Datagram Sockets"
 ///
 /// UDP Client
@@ -16,8 +19,28 @@ use std::{
 /// you can run the talker without a server, and the messages
 /// will be lost.
 ///
+/// No need for [`crate::partial::sendall`] here: a datagram is sent whole, or not at all.
+///
+/// The words of `message` are sent as text, or with `pack` as one value per field of
+/// the format, see [`serialize`]. `socket-listener --unpack` reads them back.
+///
 /// Original: [talker.c](https://beej.us/guide/bgnet/examples/talker.c)
-pub fn sockettalker(host: IpAddr, port: u16, message: String) {
+pub fn sockettalker(host: IpAddr, port: u16, message: Vec<String>, pack: Option<String>) {
+    let payload = match pack {
+        Some(format) => {
+            match serialize::parse_values(&format, &message)
+                .and_then(|values| serialize::pack(&format, &values))
+            {
+                Ok(payload) => payload,
+                Err(e) => {
+                    eprintln!("talker: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        None => message.join(" ").into_bytes(),
+    };
+
     match host {
         IpAddr::V4(addr) => {
             let socket = SocketAddrV4::new(addr, port);
@@ -32,7 +55,7 @@ pub fn sockettalker(host: IpAddr, port: u16, message: String) {
 
             nix::sys::socket::sendto(
                 sockfd.as_raw_fd(),
-                message.as_bytes(),
+                &payload,
                 &socket,
                 nix::sys::socket::MsgFlags::empty(),
             )
@@ -51,7 +74,7 @@ pub fn sockettalker(host: IpAddr, port: u16, message: String) {
 
             nix::sys::socket::sendto(
                 sockfd.as_raw_fd(),
-                message.as_bytes(),
+                &payload,
                 &socket,
                 nix::sys::socket::MsgFlags::empty(),
             )
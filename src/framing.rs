@@ -0,0 +1,185 @@
+//! Section 7.6 "Son of Data Encapsulation"
+//!
+//! TCP is a stream of bytes: one `recv` can hold half a message, or the end of one and the start
+//! of the next. A [`FrameCodec`] buffers what we receive on a connection and hands back whole
+//! messages once they are complete.
+
+use std::{error, fmt};
+
+use crate::types::Framing;
+
+/// Default for [`FrameCodec::max_size`], the most a `u16` prefix can describe
+pub const MAX_FRAME_SIZE: usize = u16::MAX as usize;
+
+/// A frame we can't encode or decode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// The frame is bigger than the maximum size, or than its length prefix can describe
+    TooLarge { len: usize, max: usize },
+    /// A newline in a frame to send with [`Framing::Line`]
+    Newline,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::TooLarge { len, max } => {
+                write!(f, "frame of {} bytes is larger than {} bytes", len, max)
+            }
+            FrameError::Newline => write!(f, "newline inside a line frame"),
+        }
+    }
+}
+
+impl error::Error for FrameError {}
+
+/// Splits the bytes of a connection into frames, and turns frames back into bytes.
+///
+/// Feed it with [`FrameCodec::push`] after every `recv`, then call [`FrameCodec::decode`]
+/// until it returns `None`.
+#[derive(Debug, Clone)]
+pub struct FrameCodec {
+    framing: Framing,
+    max_size: usize,
+    buf: Vec<u8>,
+}
+
+impl FrameCodec {
+    pub fn new(framing: Framing, max_size: usize) -> Self {
+        FrameCodec {
+            framing,
+            max_size,
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Biggest payload we accept, the length prefix may lower it
+    pub fn max_size(&self) -> usize {
+        match self.framing {
+            Framing::U16 => self.max_size.min(u16::MAX as usize),
+            Framing::U32 => self.max_size.min(u32::MAX as usize),
+            Framing::Raw | Framing::Line => self.max_size,
+        }
+    }
+
+    /// Bytes received but not decoded yet
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Append what we just received
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// The next complete frame, without its prefix or newline.
+    ///
+    /// `None` means we need more bytes. An error means the peer doesn't play by the rules,
+    /// the connection should be closed.
+    pub fn decode(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        let max = self.max_size();
+        match self.framing {
+            Framing::Raw => {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                // no boundaries to keep, so a big chunk is cut to the maximum size
+                let len = self.buf.len().min(max);
+                if len == 0 {
+                    return Err(FrameError::TooLarge {
+                        len: self.buf.len(),
+                        max,
+                    });
+                }
+                Ok(Some(self.buf.drain(..len).collect()))
+            }
+            Framing::Line => {
+                let Some(end) = self.buf.iter().position(|&b| b == b'\n') else {
+                    // no need to wait for a newline that would come too late
+                    if self.buf.len() > max {
+                        return Err(FrameError::TooLarge {
+                            len: self.buf.len(),
+                            max,
+                        });
+                    }
+                    return Ok(None);
+                };
+                let mut frame: Vec<u8> = self.buf.drain(..=end).collect();
+                frame.pop();
+                // telnet ends its lines with "\r\n"
+                if frame.last() == Some(&b'\r') {
+                    frame.pop();
+                }
+                if frame.len() > max {
+                    return Err(FrameError::TooLarge {
+                        len: frame.len(),
+                        max,
+                    });
+                }
+                Ok(Some(frame))
+            }
+            Framing::U16 | Framing::U32 => {
+                let header = self.header_len();
+                if self.buf.len() < header {
+                    return Ok(None);
+                }
+                // network byte order, like everything on the wire
+                let len = self.buf[..header]
+                    .iter()
+                    .fold(0usize, |len, &b| len << 8 | b as usize);
+                if len > max {
+                    return Err(FrameError::TooLarge { len, max });
+                }
+                if self.buf.len() < header + len {
+                    return Ok(None);
+                }
+                let frame = self.buf[header..header + len].to_vec();
+                self.buf.drain(..header + len);
+                Ok(Some(frame))
+            }
+        }
+    }
+
+    /// The bytes to send for `frame`
+    pub fn encode(&self, frame: &[u8]) -> Result<Vec<u8>, FrameError> {
+        let max = self.max_size();
+        if frame.len() > max {
+            return Err(FrameError::TooLarge {
+                len: frame.len(),
+                max,
+            });
+        }
+        let mut out = Vec::with_capacity(self.header_len() + frame.len() + 1);
+        match self.framing {
+            Framing::Raw => out.extend_from_slice(frame),
+            Framing::Line => {
+                if frame.contains(&b'\n') {
+                    return Err(FrameError::Newline);
+                }
+                out.extend_from_slice(frame);
+                out.push(b'\n');
+            }
+            Framing::U16 => {
+                out.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+                out.extend_from_slice(frame);
+            }
+            Framing::U32 => {
+                out.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+                out.extend_from_slice(frame);
+            }
+        }
+        Ok(out)
+    }
+
+    fn header_len(&self) -> usize {
+        match self.framing {
+            Framing::U16 => 2,
+            Framing::U32 => 4,
+            Framing::Raw | Framing::Line => 0,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! The network interfaces of the host, from `getifaddrs`
+//!
+//! Section 7.7 "Broadcast Packets—Hello, World!" needs the broadcast address of a subnet:
+//! it's the address of the interface with all the host bits set, the ones the netmask clears.
+
+use std::{io, net::Ipv4Addr};
+
+use nix::net::if_::InterfaceFlags;
+
+/// An IPv4 address of an interface, with its netmask
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv4Interface {
+    /// Name of the interface, like `eth0`
+    pub name: String,
+    pub addr: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+    /// `IFF_BROADCAST`: the interface supports broadcast, loopback and point-to-point links don't
+    pub can_broadcast: bool,
+}
+
+impl Ipv4Interface {
+    /// The directed broadcast address of the subnet of this interface
+    pub fn broadcast(&self) -> Ipv4Addr {
+        directed_broadcast(self.addr, self.netmask)
+    }
+}
+
+/// `addr | ~netmask`: `192.168.1.42` with `255.255.255.0` gives `192.168.1.255`
+pub fn directed_broadcast(addr: Ipv4Addr, netmask: Ipv4Addr) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from(addr) | !u32::from(netmask))
+}
+
+/// Every IPv4 address of the interfaces that are up, in the order of `getifaddrs`
+pub fn ipv4_interfaces() -> io::Result<Vec<Ipv4Interface>> {
+    let mut interfaces = Vec::new();
+    for ifaddr in nix::ifaddrs::getifaddrs()? {
+        if !ifaddr.flags.contains(InterfaceFlags::IFF_UP) {
+            continue;
+        }
+        let addr = ifaddr.address.as_ref().and_then(|a| a.as_sockaddr_in());
+        let netmask = ifaddr.netmask.as_ref().and_then(|a| a.as_sockaddr_in());
+        // the other families show up too, and an address may come without a netmask
+        let (Some(addr), Some(netmask)) = (addr, netmask) else {
+            continue;
+        };
+        interfaces.push(Ipv4Interface {
+            name: ifaddr.interface_name,
+            addr: addr.ip(),
+            netmask: netmask.ip(),
+            can_broadcast: ifaddr.flags.contains(InterfaceFlags::IFF_BROADCAST),
+        });
+    }
+    Ok(interfaces)
+}
+
+/// The first IPv4 address of the interface called `name`
+pub fn ipv4_interface(name: &str) -> io::Result<Option<Ipv4Interface>> {
+    Ok(ipv4_interfaces()?.into_iter().find(|i| i.name == name))
+}
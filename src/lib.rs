@@ -1,4 +1,17 @@
 pub mod types;
 pub mod builders;
+pub mod resolver;
+pub mod socket;
+pub mod partial;
+pub mod framing;
+pub mod serialize;
+pub mod byteorder;
+pub mod shutdown;
+pub mod reactor;
+pub mod connect;
+pub mod dns;
+pub mod interfaces;
+pub mod multicast;
+pub mod discovery;
 pub mod cli;
 pub mod examples;
@@ -1,35 +1,226 @@
+use std::{fs, process::ExitCode, time::Duration};
+
 use beej_rs::{
-    cli::{Cli, Commands},
+    builders::{AddrInfo, StreamClient, StreamServer},
+    cli::{self, Cli, Commands},
+    discovery::{self, Announcement},
     examples,
+    framing::FrameCodec,
+    types::{Family, Flags},
 };
 
 use clap::Parser;
 
-fn main() {
-    let cli = Cli::parse();
+/// `sysexits.h`: the command was used incorrectly
+const EX_USAGE: u8 = 64;
+
+/// `sysexits.h`: an input file did not exist or was not readable
+const EX_NOINPUT: u8 = 66;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse_from(cli::legacy_args(std::env::args_os()));
+    let prefix = cli.command.error_prefix();
 
-    match cli.command {
+    let result = match cli.command {
         Commands::ShowIp {
             host,
             family,
             service,
-        } => examples::showip(host, family, service),
-        Commands::StreamServer => examples::streamserver(),
-        Commands::StreamClient { host } => examples::streamclient(host),
-        Commands::SocketListener { port, family } => examples::socketlistener(port, family),
+            socktype,
+            flags,
+            format,
+            resolver,
+            hosts_file,
+        } => {
+            let hints = AddrInfo::builder()
+                .family(family)
+                .socktype(socktype)
+                .flags(flags.into_iter().collect::<Flags>())
+                .build();
+            examples::showip(host, service, hints, format, resolver, hosts_file)
+        }
+        Commands::Reverse { ip, port, flags } => {
+            examples::reverse(ip, port, flags.into_iter().collect())
+        }
+        Commands::StreamServer {
+            host,
+            port,
+            family,
+            backlog,
+            banner,
+            banner_file,
+            mode,
+            workers,
+        } => {
+            let banner = match banner_file {
+                Some(path) => match fs::read(&path) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        eprintln!("server: {}: {}", path.display(), e);
+                        return ExitCode::from(EX_NOINPUT);
+                    }
+                },
+                None => banner.into_bytes(),
+            };
+            let config = StreamServer::builder()
+                .host(host)
+                .port(port)
+                .family(family)
+                .backlog(backlog)
+                .banner(banner)
+                .mode(mode)
+                .workers(workers.into())
+                .build();
+            examples::streamserver(config)
+        }
+        Commands::StreamClient {
+            host,
+            port,
+            family,
+            max_data_size,
+            output,
+            max_bytes,
+            timeout,
+            connect_timeout,
+            happy_eyeballs,
+            attempt_delay,
+            resolver,
+            hosts_file,
+        } => {
+            let config = StreamClient::builder()
+                .host(host)
+                .port(port)
+                .family(family)
+                .max_data_size(max_data_size as usize)
+                .output(output)
+                .max_bytes(max_bytes)
+                .timeout(timeout.map(Duration::from_millis))
+                .connect_timeout(connect_timeout.map(Duration::from_millis))
+                .happy_eyeballs(happy_eyeballs)
+                .attempt_delay(Duration::from_millis(attempt_delay))
+                .resolver(resolver)
+                .hosts_file(hosts_file)
+                .build();
+            examples::streamclient(config)
+        }
+        Commands::SocketListener {
+            port,
+            family,
+            unpack,
+        } => {
+            examples::socketlistener(port, family, unpack);
+            Ok(())
+        }
         Commands::SocketTalker {
             host,
             port,
+            pack,
             message,
-        } => examples::sockettalker(host, port, message),
-        Commands::PollStdIn => examples::pollstdin(),
-        Commands::PollServer { port } => examples::pollserver(port),
-        Commands::Select => examples::select(),
-        Commands::SelectServer { port } => examples::select_server(port),
+        } => {
+            examples::sockettalker(host, port, message, pack);
+            Ok(())
+        }
+        Commands::PollStdIn => {
+            examples::pollstdin();
+            Ok(())
+        }
+        Commands::ChatServer {
+            port,
+            goodbye,
+            framing,
+            max_frame_size,
+            backend,
+            announce,
+            discovery_port,
+            discovery_interface,
+        } => {
+            let codec = FrameCodec::new(framing, max_frame_size);
+            let goodbye = match examples::goodbye_message(&codec, &goodbye) {
+                Ok(goodbye) => goodbye,
+                Err(e) => {
+                    eprintln!("chat-server: --goodbye: {}", e);
+                    return ExitCode::from(EX_USAGE);
+                }
+            };
+            if let Some(name) = announce {
+                // the chat server listens on [::], which takes IPv4 clients too
+                let announcement = Announcement::new(name, port, Family::Unspecified);
+                discovery::announce(announcement, discovery_port, discovery_interface.as_deref());
+            }
+            examples::chatserver(port, goodbye, codec, backend);
+            Ok(())
+        }
+        Commands::ChatClient { host, port, family } => examples::chatclient(host, port, family),
+        Commands::Select => {
+            examples::select();
+            Ok(())
+        }
+        Commands::Ieee754 {
+            values,
+            bits,
+            expbits,
+            check,
+        } => {
+            examples::ieee754(values, bits, expbits, check);
+            Ok(())
+        }
         Commands::Broadcaster {
             host,
             port,
             message,
-        } => examples::broadcaster(host, port, message),
+            interface,
+            all_interfaces,
+        } => {
+            examples::broadcaster(host, port, message, interface, all_interfaces);
+            Ok(())
+        }
+        Commands::BroadcastListener { port } => {
+            examples::broadcastlistener(port);
+            Ok(())
+        }
+        Commands::Discover {
+            family,
+            port,
+            interface,
+            broadcast,
+            timeout,
+        } => {
+            examples::discover(
+                family,
+                port,
+                interface,
+                broadcast,
+                Duration::from_millis(timeout),
+            );
+            Ok(())
+        }
+        Commands::MulticastSend {
+            group,
+            message,
+            port,
+            hops,
+            no_loop,
+            interface,
+        } => {
+            examples::multicast_send(group, port, message, hops, !no_loop, interface);
+            Ok(())
+        }
+        Commands::MulticastRecv {
+            group,
+            port,
+            interface,
+            source,
+        } => {
+            examples::multicast_recv(group, port, interface, source);
+            Ok(())
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}: {}", prefix, e);
+            cli::exit_code(&e)
+        }
     }
 }
@@ -0,0 +1,249 @@
+//! Multicast, the big brother of section 7.7 "Broadcast Packets—Hello, World!"
+//!
+//! A datagram sent to a multicast group reaches the hosts that joined the group, instead of
+//! every host of the subnet. `nix` has the IPv4 options; the ones it lacks are below, as
+//! [`SetSockOpt`] implementations so they go through [`setsockopt`] like the others.
+//!
+//! Interfaces are chosen by name: IPv4 options want one of its addresses, IPv6 ones its index.
+
+use std::{
+    io, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::fd::{AsFd, AsRawFd},
+};
+
+use nix::{
+    errno::Errno,
+    sys::socket::{setsockopt, sockopt, IpMembershipRequest, SetSockOpt},
+};
+
+use crate::interfaces;
+
+fn set<F: AsFd, T>(fd: &F, level: libc::c_int, name: libc::c_int, val: &T) -> nix::Result<()> {
+    let res = unsafe {
+        libc::setsockopt(
+            fd.as_fd().as_raw_fd(),
+            level,
+            name,
+            val as *const T as *const libc::c_void,
+            mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    Errno::result(res).map(drop)
+}
+
+/// `IP_MULTICAST_IF`: the interface, given by one of its addresses, that sends our IPv4 multicast
+#[derive(Debug, Clone, Copy)]
+pub struct IpMulticastIf;
+
+impl SetSockOpt for IpMulticastIf {
+    type Val = Ipv4Addr;
+
+    fn set<F: AsFd>(&self, fd: &F, val: &Ipv4Addr) -> nix::Result<()> {
+        let addr = libc::in_addr {
+            s_addr: u32::from_ne_bytes(val.octets()),
+        };
+        set(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_IF, &addr)
+    }
+}
+
+/// `IPV6_MULTICAST_IF`: the index of the interface that sends our IPv6 multicast
+#[derive(Debug, Clone, Copy)]
+pub struct Ipv6MulticastIf;
+
+impl SetSockOpt for Ipv6MulticastIf {
+    type Val = u32;
+
+    fn set<F: AsFd>(&self, fd: &F, val: &u32) -> nix::Result<()> {
+        set(fd, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_IF, val)
+    }
+}
+
+/// `IPV6_MULTICAST_LOOP`: whether our own IPv6 multicast comes back to the sockets of this host
+#[derive(Debug, Clone, Copy)]
+pub struct Ipv6MulticastLoop;
+
+impl SetSockOpt for Ipv6MulticastLoop {
+    type Val = bool;
+
+    fn set<F: AsFd>(&self, fd: &F, val: &bool) -> nix::Result<()> {
+        set(
+            fd,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MULTICAST_LOOP,
+            &libc::c_uint::from(*val),
+        )
+    }
+}
+
+/// `IPV6_ADD_MEMBERSHIP` on a given interface, `nix`'s [`sockopt::Ipv6AddMembership`] always
+/// lets the kernel choose
+#[derive(Debug, Clone, Copy)]
+pub struct Ipv6AddMembership;
+
+/// An IPv6 group and the index of the interface to join it on, `0` for any
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv6Membership {
+    pub group: Ipv6Addr,
+    pub interface: u32,
+}
+
+impl SetSockOpt for Ipv6AddMembership {
+    type Val = Ipv6Membership;
+
+    fn set<F: AsFd>(&self, fd: &F, val: &Ipv6Membership) -> nix::Result<()> {
+        let mreq = libc::ipv6_mreq {
+            ipv6mr_multiaddr: libc::in6_addr {
+                s6_addr: val.group.octets(),
+            },
+            ipv6mr_interface: val.interface,
+        };
+        set(fd, libc::IPPROTO_IPV6, libc::IPV6_ADD_MEMBERSHIP, &mreq)
+    }
+}
+
+/// `MCAST_JOIN_SOURCE_GROUP`: join a group, but only hear one source (RFC 4607).
+///
+/// Works for both families, where `IP_ADD_SOURCE_MEMBERSHIP` is IPv4 only.
+#[derive(Debug, Clone, Copy)]
+pub struct JoinSourceGroup;
+
+/// A group, the only source we want to hear in it, and the interface index, `0` for any
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceMembership {
+    pub group: IpAddr,
+    pub source: IpAddr,
+    pub interface: u32,
+}
+
+impl SetSockOpt for JoinSourceGroup {
+    type Val = SourceMembership;
+
+    fn set<F: AsFd>(&self, fd: &F, val: &SourceMembership) -> nix::Result<()> {
+        let level = match (val.group, val.source) {
+            (IpAddr::V4(_), IpAddr::V4(_)) => libc::IPPROTO_IP,
+            (IpAddr::V6(_), IpAddr::V6(_)) => libc::IPPROTO_IPV6,
+            _ => return Err(Errno::EINVAL),
+        };
+        let req = libc::group_source_req {
+            gsr_interface: val.interface,
+            gsr_group: sockaddr_storage(val.group),
+            gsr_source: sockaddr_storage(val.source),
+        };
+        set(fd, level, libc::MCAST_JOIN_SOURCE_GROUP, &req)
+    }
+}
+
+fn sockaddr_storage(addr: IpAddr) -> libc::sockaddr_storage {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    // sockaddr_storage is big enough and aligned for every sockaddr
+    match addr {
+        IpAddr::V4(addr) => {
+            let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_addr = libc::in_addr {
+                s_addr: u32::from_ne_bytes(addr.octets()),
+            };
+        }
+        IpAddr::V6(addr) => {
+            let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_addr = libc::in6_addr {
+                s6_addr: addr.octets(),
+            };
+        }
+    }
+    storage
+}
+
+/// The index of the interface called `name`
+pub fn interface_index(name: &str) -> io::Result<u32> {
+    Ok(nix::net::if_::if_nametoindex(name)?)
+}
+
+/// The first IPv4 address of the interface called `name`
+pub fn interface_addr(name: &str) -> io::Result<Ipv4Addr> {
+    match interfaces::ipv4_interface(name)? {
+        Some(interface) => Ok(interface.addr),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no IPv4 interface named {}", name),
+        )),
+    }
+}
+
+fn check_group(group: IpAddr) -> io::Result<()> {
+    if group.is_multicast() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a multicast address", group),
+        ))
+    }
+}
+
+/// Join `group` on `interface`, or on the one the kernel picks.
+///
+/// With a `source`, only the datagrams it sends to the group are received.
+pub fn join<F: AsFd>(
+    fd: &F,
+    group: IpAddr,
+    interface: Option<&str>,
+    source: Option<IpAddr>,
+) -> io::Result<()> {
+    check_group(group)?;
+    if let Some(source) = source {
+        let interface = interface.map(interface_index).transpose()?.unwrap_or(0);
+        let membership = SourceMembership {
+            group,
+            source,
+            interface,
+        };
+        setsockopt(fd, JoinSourceGroup, &membership)?;
+        return Ok(());
+    }
+    match group {
+        IpAddr::V4(group) => {
+            let interface = interface.map(interface_addr).transpose()?;
+            let mreq = IpMembershipRequest::new(group, interface);
+            setsockopt(fd, sockopt::IpAddMembership, &mreq)?;
+        }
+        IpAddr::V6(group) => {
+            let interface = interface.map(interface_index).transpose()?.unwrap_or(0);
+            setsockopt(fd, Ipv6AddMembership, &Ipv6Membership { group, interface })?;
+        }
+    }
+    Ok(())
+}
+
+/// How our multicast datagrams to `group` leave the host.
+///
+/// `hops` is the TTL for IPv4: `1` stays on the subnet. `loopback` sends a copy to the
+/// sockets of this host that joined the group.
+pub fn set_send_options<F: AsFd>(
+    fd: &F,
+    group: IpAddr,
+    hops: u8,
+    loopback: bool,
+    interface: Option<&str>,
+) -> io::Result<()> {
+    check_group(group)?;
+    match group {
+        IpAddr::V4(_) => {
+            setsockopt(fd, sockopt::IpMulticastTtl, &hops)?;
+            setsockopt(fd, sockopt::IpMulticastLoop, &loopback)?;
+            if let Some(interface) = interface {
+                setsockopt(fd, IpMulticastIf, &interface_addr(interface)?)?;
+            }
+        }
+        IpAddr::V6(_) => {
+            setsockopt(fd, sockopt::Ipv6MulticastHops, &libc::c_int::from(hops))?;
+            setsockopt(fd, Ipv6MulticastLoop, &loopback)?;
+            if let Some(interface) = interface {
+                setsockopt(fd, Ipv6MulticastIf, &interface_index(interface)?)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Section 7.4 "Handling Partial send()s"
+//!
+//! `send` and `recv` may handle fewer bytes than asked, so we loop until the whole buffer is done.
+//! `EINTR` is retried, and on a non-blocking socket `EAGAIN` waits with `poll` until we can go on.
+//! On a blocking socket `EAGAIN` means `SO_SNDTIMEO` or `SO_RCVTIMEO` expired, so it's an error.
+
+use std::{
+    error, fmt, io,
+    os::fd::{AsFd, AsRawFd},
+};
+
+use nix::{
+    errno::Errno,
+    poll::{PollFd, PollFlags, PollTimeout},
+};
+
+/// The error of [`sendall`] or [`recv_exact`], with how many bytes were handled before it
+#[derive(Debug)]
+pub struct Incomplete {
+    /// Bytes sent or received, like the `len` of the book's `sendall`
+    pub len: usize,
+    pub error: io::Error,
+}
+
+impl fmt::Display for Incomplete {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after {} bytes", self.error, self.len)
+    }
+}
+
+impl error::Error for Incomplete {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<Incomplete> for io::Error {
+    fn from(incomplete: Incomplete) -> Self {
+        io::Error::new(incomplete.error.kind(), incomplete)
+    }
+}
+
+/// After `EAGAIN`: wait until `fd` is ready for `events`, or give back `error` if it was a timeout
+fn wait<Fd: AsFd>(fd: &Fd, events: PollFlags, error: io::Error) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd.as_fd().as_raw_fd(), libc::F_GETFL) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // a timeout of a blocking socket
+    if flags & libc::O_NONBLOCK == 0 {
+        return Err(error);
+    }
+    let mut pfds = [PollFd::new(fd.as_fd(), events)];
+    match nix::poll::poll(&mut pfds, PollTimeout::NONE) {
+        Ok(_) | Err(Errno::EINTR) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Send the whole of `buf` on a connected socket.
+///
+/// A closed peer is reported as `EPIPE` instead of killing us with `SIGPIPE`.
+pub fn sendall<Fd: AsFd>(fd: Fd, buf: &[u8]) -> Result<(), Incomplete> {
+    let mut total = 0;
+    while total < buf.len() {
+        let n = unsafe {
+            libc::send(
+                fd.as_fd().as_raw_fd(),
+                buf[total..].as_ptr() as *const libc::c_void,
+                buf.len() - total,
+                libc::MSG_NOSIGNAL,
+            )
+        };
+        if n >= 0 {
+            total += n as usize;
+            continue;
+        }
+        let error = io::Error::last_os_error();
+        let waited = match error.kind() {
+            io::ErrorKind::Interrupted => Ok(()),
+            io::ErrorKind::WouldBlock => wait(&fd, PollFlags::POLLOUT, error),
+            _ => Err(error),
+        };
+        waited.map_err(|error| Incomplete { len: total, error })?;
+    }
+    Ok(())
+}
+
+/// Receive exactly `buf.len()` bytes.
+///
+/// The peer closing the connection before that is `UnexpectedEof`.
+pub fn recv_exact<Fd: AsFd>(fd: Fd, buf: &mut [u8]) -> Result<(), Incomplete> {
+    let mut total = 0;
+    while total < buf.len() {
+        let n = unsafe {
+            libc::recv(
+                fd.as_fd().as_raw_fd(),
+                buf[total..].as_mut_ptr() as *mut libc::c_void,
+                buf.len() - total,
+                0,
+            )
+        };
+        if n > 0 {
+            total += n as usize;
+            continue;
+        }
+        let error = if n == 0 {
+            io::Error::from(io::ErrorKind::UnexpectedEof)
+        } else {
+            io::Error::last_os_error()
+        };
+        let waited = match error.kind() {
+            io::ErrorKind::Interrupted => Ok(()),
+            io::ErrorKind::WouldBlock => wait(&fd, PollFlags::POLLIN, error),
+            _ => Err(error),
+        };
+        waited.map_err(|error| Incomplete { len: total, error })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,358 @@
+//! One interface over `poll`, `select` and `epoll`
+//!
+//! Sections 7.2 and 7.3 write the same chat server twice, once for each system call.
+//! An [`EventLoop`] keeps the fds we are interested in, and tells us which ones are ready:
+//! the server is written once, and the backend is a choice.
+//!
+//! Registered fds are borrowed, not owned: deregister them before closing them.
+//! `epoll` would silently forget a closed fd, `poll` and `select` would report it as invalid.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    os::fd::{AsRawFd, BorrowedFd, RawFd},
+    time::Duration,
+};
+
+use bitflags::bitflags;
+use nix::{
+    errno::Errno,
+    poll::{PollFd, PollFlags, PollTimeout},
+    sys::{
+        epoll::{Epoll, EpollCreateFlags, EpollEvent, EpollFlags},
+        select::{FdSet, FD_SETSIZE},
+        time::TimeVal,
+    },
+};
+
+use crate::types::Backend;
+
+bitflags! {
+    /// What we want to hear about an fd
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Interest: u8 {
+        /// Data to read, a connection to accept, or the peer closing
+        const READABLE = 1;
+        /// Room in the send buffer
+        const WRITABLE = 1 << 1;
+    }
+}
+
+/// An fd that is ready
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    pub fd: RawFd,
+    /// Errors and hang-ups count as readable, the next `recv` tells what happened
+    pub readable: bool,
+    pub writable: bool,
+}
+
+/// A set of fds to wait on, whatever the system call behind it
+pub trait EventLoop {
+    /// Start watching `fd`, `AlreadyExists` if it is already watched
+    fn register(&mut self, fd: BorrowedFd<'_>, interest: Interest) -> io::Result<()>;
+
+    /// Change what we want to hear about `fd`, `NotFound` if it isn't watched
+    fn reregister(&mut self, fd: BorrowedFd<'_>, interest: Interest) -> io::Result<()>;
+
+    /// Stop watching `fd`, before it is closed
+    fn deregister(&mut self, fd: BorrowedFd<'_>) -> io::Result<()>;
+
+    /// Replace `events` with the fds that are ready, waiting at most `timeout`, or forever.
+    ///
+    /// A timeout, or a signal interrupting the wait, leaves `events` empty.
+    fn wait(&mut self, events: &mut Vec<Event>, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// The backend, for the logs
+    fn backend(&self) -> Backend;
+}
+
+/// The event loop of `backend`
+pub fn new(backend: Backend) -> io::Result<Box<dyn EventLoop>> {
+    Ok(match backend {
+        Backend::Poll => Box::new(PollBackend::new()),
+        Backend::Select => Box::new(SelectBackend::new()),
+        Backend::Epoll => Box::new(EpollBackend::new()?),
+    })
+}
+
+fn already_registered(fd: RawFd) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("fd {} is already registered", fd),
+    )
+}
+
+fn not_registered(fd: RawFd) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("fd {} is not registered", fd),
+    )
+}
+
+/// Milliseconds for `poll` and `epoll_wait`, rounded up so we don't wake up just before the end
+fn millis(timeout: Option<Duration>) -> PollTimeout {
+    match timeout {
+        None => PollTimeout::NONE,
+        Some(timeout) => {
+            let millis = timeout.as_nanos().div_ceil(1_000_000);
+            PollTimeout::try_from(millis).unwrap_or(PollTimeout::MAX)
+        }
+    }
+}
+
+/// `poll()`: the whole list goes to the kernel on every call
+#[derive(Debug, Default)]
+pub struct PollBackend {
+    fds: Vec<(RawFd, Interest)>,
+}
+
+impl PollBackend {
+    pub fn new() -> Self {
+        PollBackend::default()
+    }
+
+    fn position(&self, fd: RawFd) -> io::Result<usize> {
+        self.fds
+            .iter()
+            .position(|&(registered, _)| registered == fd)
+            .ok_or_else(|| not_registered(fd))
+    }
+}
+
+impl EventLoop for PollBackend {
+    fn register(&mut self, fd: BorrowedFd<'_>, interest: Interest) -> io::Result<()> {
+        let fd = fd.as_raw_fd();
+        if self.position(fd).is_ok() {
+            return Err(already_registered(fd));
+        }
+        self.fds.push((fd, interest));
+        Ok(())
+    }
+
+    fn reregister(&mut self, fd: BorrowedFd<'_>, interest: Interest) -> io::Result<()> {
+        let i = self.position(fd.as_raw_fd())?;
+        self.fds[i].1 = interest;
+        Ok(())
+    }
+
+    fn deregister(&mut self, fd: BorrowedFd<'_>) -> io::Result<()> {
+        let i = self.position(fd.as_raw_fd())?;
+        self.fds.remove(i);
+        Ok(())
+    }
+
+    fn wait(&mut self, events: &mut Vec<Event>, timeout: Option<Duration>) -> io::Result<()> {
+        events.clear();
+        let mut pfds: Vec<PollFd> = self
+            .fds
+            .iter()
+            .map(|&(fd, interest)| {
+                let mut flags = PollFlags::empty();
+                flags.set(PollFlags::POLLIN, interest.contains(Interest::READABLE));
+                flags.set(PollFlags::POLLOUT, interest.contains(Interest::WRITABLE));
+                // SAFETY: registered fds stay open until they are deregistered
+                PollFd::new(unsafe { BorrowedFd::borrow_raw(fd) }, flags)
+            })
+            .collect();
+        match nix::poll::poll(&mut pfds, millis(timeout)) {
+            Ok(_) => {}
+            Err(Errno::EINTR) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        let hangup =
+            PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR | PollFlags::POLLNVAL;
+        for (pfd, &(fd, _)) in pfds.iter().zip(&self.fds) {
+            let revents = pfd.revents().unwrap_or(PollFlags::empty());
+            if revents.is_empty() {
+                continue;
+            }
+            events.push(Event {
+                fd,
+                readable: revents.intersects(hangup),
+                writable: revents.contains(PollFlags::POLLOUT),
+            });
+        }
+        Ok(())
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Poll
+    }
+}
+
+/// `select()`: two bit sets, rebuilt for every call, and no fd above `FD_SETSIZE`
+#[derive(Debug, Default)]
+pub struct SelectBackend {
+    // ordered, so the events come in fd order like with FD_ISSET in a loop
+    fds: BTreeMap<RawFd, Interest>,
+}
+
+impl SelectBackend {
+    pub fn new() -> Self {
+        SelectBackend::default()
+    }
+}
+
+impl EventLoop for SelectBackend {
+    fn register(&mut self, fd: BorrowedFd<'_>, interest: Interest) -> io::Result<()> {
+        let fd = fd.as_raw_fd();
+        // FD_SET past the end of the set would write over the stack
+        if fd as usize >= FD_SETSIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "fd {} is too large for select, the limit is {}",
+                    fd, FD_SETSIZE
+                ),
+            ));
+        }
+        if self.fds.contains_key(&fd) {
+            return Err(already_registered(fd));
+        }
+        self.fds.insert(fd, interest);
+        Ok(())
+    }
+
+    fn reregister(&mut self, fd: BorrowedFd<'_>, interest: Interest) -> io::Result<()> {
+        let fd = fd.as_raw_fd();
+        let registered = self.fds.get_mut(&fd).ok_or_else(|| not_registered(fd))?;
+        *registered = interest;
+        Ok(())
+    }
+
+    fn deregister(&mut self, fd: BorrowedFd<'_>) -> io::Result<()> {
+        let fd = fd.as_raw_fd();
+        self.fds.remove(&fd).ok_or_else(|| not_registered(fd))?;
+        Ok(())
+    }
+
+    fn wait(&mut self, events: &mut Vec<Event>, timeout: Option<Duration>) -> io::Result<()> {
+        events.clear();
+        let mut read_fds = FdSet::new();
+        let mut write_fds = FdSet::new();
+        for (&fd, interest) in &self.fds {
+            // SAFETY: registered fds stay open until they are deregistered
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+            if interest.contains(Interest::READABLE) {
+                read_fds.insert(fd);
+            }
+            if interest.contains(Interest::WRITABLE) {
+                write_fds.insert(fd);
+            }
+        }
+        // select may change the timeout, we make a new one every time anyway
+        let mut timeout = timeout.map(|t| TimeVal::new(t.as_secs() as _, t.subsec_micros() as _));
+        match nix::sys::select::select(
+            None,
+            Some(&mut read_fds),
+            Some(&mut write_fds),
+            None,
+            timeout.as_mut(),
+        ) {
+            Ok(_) => {}
+            Err(Errno::EINTR) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        for &fd in self.fds.keys() {
+            // SAFETY: as above
+            let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+            let readable = read_fds.contains(borrowed);
+            let writable = write_fds.contains(borrowed);
+            if readable || writable {
+                events.push(Event {
+                    fd,
+                    readable,
+                    writable,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Select
+    }
+}
+
+/// `epoll`: the kernel keeps the interest list, each wait only returns the ready fds
+#[derive(Debug)]
+pub struct EpollBackend {
+    epoll: Epoll,
+    // room for the ready fds, grows with the number of registered fds
+    ready: Vec<EpollEvent>,
+    registered: usize,
+}
+
+impl EpollBackend {
+    pub fn new() -> io::Result<Self> {
+        Ok(EpollBackend {
+            epoll: Epoll::new(EpollCreateFlags::EPOLL_CLOEXEC)?,
+            ready: Vec::new(),
+            registered: 0,
+        })
+    }
+
+    fn event(fd: RawFd, interest: Interest) -> EpollEvent {
+        let mut flags = EpollFlags::empty();
+        flags.set(EpollFlags::EPOLLIN, interest.contains(Interest::READABLE));
+        flags.set(EpollFlags::EPOLLOUT, interest.contains(Interest::WRITABLE));
+        // level-triggered, like poll and select: an fd stays ready until we read it all
+        EpollEvent::new(flags, fd as u64)
+    }
+}
+
+impl EventLoop for EpollBackend {
+    fn register(&mut self, fd: BorrowedFd<'_>, interest: Interest) -> io::Result<()> {
+        match self.epoll.add(fd, Self::event(fd.as_raw_fd(), interest)) {
+            Ok(()) => {}
+            Err(Errno::EEXIST) => return Err(already_registered(fd.as_raw_fd())),
+            Err(e) => return Err(e.into()),
+        }
+        self.registered += 1;
+        Ok(())
+    }
+
+    fn reregister(&mut self, fd: BorrowedFd<'_>, interest: Interest) -> io::Result<()> {
+        let mut event = Self::event(fd.as_raw_fd(), interest);
+        match self.epoll.modify(fd, &mut event) {
+            Ok(()) => Ok(()),
+            Err(Errno::ENOENT) => Err(not_registered(fd.as_raw_fd())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn deregister(&mut self, fd: BorrowedFd<'_>) -> io::Result<()> {
+        match self.epoll.delete(fd) {
+            Ok(()) => {}
+            Err(Errno::ENOENT) => return Err(not_registered(fd.as_raw_fd())),
+            Err(e) => return Err(e.into()),
+        }
+        self.registered -= 1;
+        Ok(())
+    }
+
+    fn wait(&mut self, events: &mut Vec<Event>, timeout: Option<Duration>) -> io::Result<()> {
+        events.clear();
+        self.ready
+            .resize(self.registered.max(1), EpollEvent::empty());
+        let n = match self.epoll.wait(&mut self.ready, millis(timeout)) {
+            Ok(n) => n,
+            Err(Errno::EINTR) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let hangup = EpollFlags::EPOLLIN | EpollFlags::EPOLLHUP | EpollFlags::EPOLLERR;
+        for event in &self.ready[..n] {
+            events.push(Event {
+                fd: event.data() as RawFd,
+                readable: event.events().intersects(hangup),
+                writable: event.events().contains(EpollFlags::EPOLLOUT),
+            });
+        }
+        Ok(())
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Epoll
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    error::Error,
+    ffi::{CStr, CString, NulError},
+    fmt::Display,
+    marker::PhantomData,
+    net::SocketAddr,
+    ptr,
+};
+
+use nix::errno::Errno;
+use socket2::SockAddr;
+
+use crate::{
+    builders::AddrInfo,
+    types::{Family, NameInfoFlags, Protocol, SocketType, UnknownConstant},
+};
+
+// glibc extensions that are not exposed by the libc crate
+const EAI_ADDRFAMILY: libc::c_int = -9;
+const EAI_INPROGRESS: libc::c_int = -100;
+const EAI_CANCELED: libc::c_int = -101;
+const EAI_NOTCANCELED: libc::c_int = -102;
+const EAI_ALLDONE: libc::c_int = -103;
+const EAI_INTR: libc::c_int = -104;
+const EAI_IDN_ENCODE: libc::c_int = -105;
+
+// From netdb.h, big enough for any service name
+const NI_MAXSERV: usize = 32;
+
+/// Errors returned by `getaddrinfo` and `getnameinfo`, one variant per `EAI_*` code
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    /// `EAI_ADDRFAMILY`: the host has no address in the requested family
+    AddrFamily,
+    /// `EAI_AGAIN`: temporary failure in name resolution, try again later
+    Again,
+    /// `EAI_BADFLAGS`: invalid value for `ai_flags`
+    BadFlags,
+    /// `EAI_FAIL`: non-recoverable failure in name resolution
+    Fail,
+    /// `EAI_FAMILY`: `ai_family` not supported
+    Family,
+    /// `EAI_MEMORY`: memory allocation failure
+    Memory,
+    /// `EAI_NODATA`: the host exists but has no address
+    NoData,
+    /// `EAI_NONAME`: name or service not known
+    NoName,
+    /// `EAI_OVERFLOW`: argument buffer overflow
+    Overflow,
+    /// `EAI_SERVICE`: the service is not supported for `ai_socktype`
+    Service,
+    /// `EAI_SOCKTYPE`: `ai_socktype` not supported
+    SockType,
+    /// `EAI_SYSTEM`: system error, the cause is in `errno`
+    System(Errno),
+    /// `EAI_INPROGRESS`: processing request in progress
+    InProgress,
+    /// `EAI_CANCELED`: request canceled
+    Canceled,
+    /// `EAI_NOTCANCELED`: request not canceled
+    NotCanceled,
+    /// `EAI_ALLDONE`: all requests done
+    AllDone,
+    /// `EAI_INTR`: interrupted by a signal
+    Interrupted,
+    /// `EAI_IDN_ENCODE`: parameter string not correctly encoded
+    IdnEncode,
+    /// A code this enum doesn't know about
+    Unknown(libc::c_int),
+    /// The host or service contains a NUL byte and can't be passed to C
+    InvalidName(NulError),
+}
+
+impl ResolveError {
+    /// Build the error from the return value of `getaddrinfo`.
+    /// Must be called right after the call, so `errno` is still meaningful.
+    pub fn from_code(code: libc::c_int) -> Self {
+        match code {
+            EAI_ADDRFAMILY => ResolveError::AddrFamily,
+            libc::EAI_AGAIN => ResolveError::Again,
+            libc::EAI_BADFLAGS => ResolveError::BadFlags,
+            libc::EAI_FAIL => ResolveError::Fail,
+            libc::EAI_FAMILY => ResolveError::Family,
+            libc::EAI_MEMORY => ResolveError::Memory,
+            libc::EAI_NODATA => ResolveError::NoData,
+            libc::EAI_NONAME => ResolveError::NoName,
+            libc::EAI_OVERFLOW => ResolveError::Overflow,
+            libc::EAI_SERVICE => ResolveError::Service,
+            libc::EAI_SOCKTYPE => ResolveError::SockType,
+            libc::EAI_SYSTEM => ResolveError::System(Errno::last()),
+            EAI_INPROGRESS => ResolveError::InProgress,
+            EAI_CANCELED => ResolveError::Canceled,
+            EAI_NOTCANCELED => ResolveError::NotCanceled,
+            EAI_ALLDONE => ResolveError::AllDone,
+            EAI_INTR => ResolveError::Interrupted,
+            EAI_IDN_ENCODE => ResolveError::IdnEncode,
+            code => ResolveError::Unknown(code),
+        }
+    }
+
+    /// The `EAI_*` code, `None` for errors that don't come from libc
+    pub fn code(&self) -> Option<libc::c_int> {
+        let code = match self {
+            ResolveError::AddrFamily => EAI_ADDRFAMILY,
+            ResolveError::Again => libc::EAI_AGAIN,
+            ResolveError::BadFlags => libc::EAI_BADFLAGS,
+            ResolveError::Fail => libc::EAI_FAIL,
+            ResolveError::Family => libc::EAI_FAMILY,
+            ResolveError::Memory => libc::EAI_MEMORY,
+            ResolveError::NoData => libc::EAI_NODATA,
+            ResolveError::NoName => libc::EAI_NONAME,
+            ResolveError::Overflow => libc::EAI_OVERFLOW,
+            ResolveError::Service => libc::EAI_SERVICE,
+            ResolveError::SockType => libc::EAI_SOCKTYPE,
+            ResolveError::System(_) => libc::EAI_SYSTEM,
+            ResolveError::InProgress => EAI_INPROGRESS,
+            ResolveError::Canceled => EAI_CANCELED,
+            ResolveError::NotCanceled => EAI_NOTCANCELED,
+            ResolveError::AllDone => EAI_ALLDONE,
+            ResolveError::Interrupted => EAI_INTR,
+            ResolveError::IdnEncode => EAI_IDN_ENCODE,
+            ResolveError::Unknown(code) => *code,
+            ResolveError::InvalidName(_) => return None,
+        };
+        Some(code)
+    }
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::System(errno) => write!(f, "System error: {}", errno.desc()),
+            ResolveError::InvalidName(e) => write!(f, "Invalid name: {}", e),
+            _ => {
+                let code = self.code().expect("every EAI error has a code");
+                // gai_strerror returns a static string, even for unknown codes
+                let msg = unsafe { CStr::from_ptr(libc::gai_strerror(code)) };
+                write!(f, "{}", msg.to_string_lossy())
+            }
+        }
+    }
+}
+
+impl Error for ResolveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResolveError::System(errno) => Some(errno),
+            ResolveError::InvalidName(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<NulError> for ResolveError {
+    fn from(e: NulError) -> Self {
+        ResolveError::InvalidName(e)
+    }
+}
+
+/// Owning list of results returned by `getaddrinfo`.
+///
+/// The linked list allocated by libc is released with `freeaddrinfo` when
+/// the list is dropped, so there is no need to free it by hand like in the book.
+pub struct AddrInfoList {
+    head: *mut libc::addrinfo,
+}
+
+impl AddrInfoList {
+    /// Walk the `ai_next` chain without taking ownership of the list
+    pub fn iter(&self) -> AddrInfoIter<'_> {
+        AddrInfoIter {
+            next: self.head,
+            _list: PhantomData,
+        }
+    }
+}
+
+impl Drop for AddrInfoList {
+    fn drop(&mut self) {
+        if !self.head.is_null() {
+            unsafe { libc::freeaddrinfo(self.head) };
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a AddrInfoList {
+    type Item = AddrInfoEntry;
+    type IntoIter = AddrInfoIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the entries of an [`AddrInfoList`]
+pub struct AddrInfoIter<'a> {
+    next: *const libc::addrinfo,
+    _list: PhantomData<&'a AddrInfoList>,
+}
+
+impl Iterator for AddrInfoIter<'_> {
+    type Item = AddrInfoEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the pointer comes from getaddrinfo and the list outlives the iterator
+        let ai = unsafe { self.next.as_ref()? };
+        self.next = ai.ai_next;
+
+        let ((), sockaddr) = unsafe {
+            SockAddr::try_init(|storage, len| {
+                *len = ai.ai_addrlen as _;
+                ptr::copy_nonoverlapping(
+                    ai.ai_addr as *const u8,
+                    storage as *mut u8,
+                    ai.ai_addrlen as usize,
+                );
+                Ok(())
+            })
+        }
+        .expect("sockaddr_storage is big enough for any address");
+
+        // only the first entry carries it, and only with AI_CANONNAME
+        let canonname = unsafe { ai.ai_canonname.as_ref() }.map(|name| {
+            unsafe { CStr::from_ptr(name) }
+                .to_string_lossy()
+                .into_owned()
+        });
+
+        Some(AddrInfoEntry {
+            family: ai.ai_family,
+            socktype: ai.ai_socktype,
+            protocol: ai.ai_protocol,
+            sockaddr,
+            canonname,
+        })
+    }
+}
+
+/// A single result of `getaddrinfo`, copied out of the libc list
+#[derive(Debug, Clone)]
+pub struct AddrInfoEntry {
+    family: libc::c_int,
+    socktype: libc::c_int,
+    protocol: libc::c_int,
+    sockaddr: SockAddr,
+    canonname: Option<String>,
+}
+
+impl AddrInfoEntry {
+    pub fn family(&self) -> Family {
+        self.family.into()
+    }
+
+    pub fn socktype(&self) -> Result<SocketType, UnknownConstant> {
+        self.socktype.try_into()
+    }
+
+    pub fn protocol(&self) -> Result<Protocol, UnknownConstant> {
+        self.protocol.try_into()
+    }
+
+    /// Raw values as expected by `socket(2)`: family, socktype and protocol
+    pub fn socket_args(&self) -> (libc::c_int, libc::c_int, libc::c_int) {
+        (self.family, self.socktype, self.protocol)
+    }
+
+    pub fn sockaddr(&self) -> &SockAddr {
+        &self.sockaddr
+    }
+
+    /// The address as a std type, `None` if it's not IPv4 or IPv6
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        self.sockaddr.as_socket()
+    }
+
+    /// Canonical name of the host, set on the first entry when using `AI_CANONNAME`
+    pub fn canonname(&self) -> Option<&str> {
+        self.canonname.as_deref()
+    }
+}
+
+/// Safe version of `getaddrinfo`
+///
+/// Either `node` or `service` can be omitted, like passing `NULL` in C.
+/// A missing `node` combined with [`Flag::Passive`](crate::types::Flag::Passive)
+/// gives you the wildcard address, ready to `bind`.
+pub fn getaddrinfo(
+    node: Option<&str>,
+    service: Option<&str>,
+    hints: AddrInfo,
+) -> Result<AddrInfoList, ResolveError> {
+    let node = node.map(CString::new).transpose()?;
+    let service = service.map(CString::new).transpose()?;
+    let hints: libc::addrinfo = hints.into();
+
+    let mut head = ptr::null_mut();
+    let rv = unsafe {
+        libc::getaddrinfo(
+            node.as_ref().map_or(ptr::null(), |n| n.as_ptr()),
+            service.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            &hints,
+            &mut head,
+        )
+    };
+
+    if rv != 0 {
+        return Err(ResolveError::from_code(rv));
+    }
+
+    Ok(AddrInfoList { head })
+}
+
+/// Host and service names returned by [`getnameinfo`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameInfo {
+    pub host: String,
+    pub service: String,
+}
+
+/// Safe version of `getnameinfo`, the replacement for `gethostbyaddr` and `getservbyport`
+///
+/// Without [`NameInfoFlags::NAMEREQD`], an address without a name gives you
+/// the numeric form of the address instead of an error.
+pub fn getnameinfo(addr: &SockAddr, flags: NameInfoFlags) -> Result<NameInfo, ResolveError> {
+    let mut host = [0 as libc::c_char; libc::NI_MAXHOST as usize];
+    let mut service = [0 as libc::c_char; NI_MAXSERV];
+
+    let rv = unsafe {
+        libc::getnameinfo(
+            addr.as_ptr(),
+            addr.len(),
+            host.as_mut_ptr(),
+            host.len() as libc::socklen_t,
+            service.as_mut_ptr(),
+            service.len() as libc::socklen_t,
+            flags.bits(),
+        )
+    };
+
+    if rv != 0 {
+        return Err(ResolveError::from_code(rv));
+    }
+
+    // SAFETY: getnameinfo NUL terminates both buffers on success
+    let (host, service) = unsafe {
+        (
+            CStr::from_ptr(host.as_ptr()),
+            CStr::from_ptr(service.as_ptr()),
+        )
+    };
+    Ok(NameInfo {
+        host: host.to_string_lossy().into_owned(),
+        service: service.to_string_lossy().into_owned(),
+    })
+}
+
+/// Port of a service from `/etc/services`, numeric services are returned as is
+pub fn getservbyname(service: &str, socktype: SocketType) -> Result<u16, ResolveError> {
+    if let Ok(port) = service.parse() {
+        return Ok(port);
+    }
+    let name = CString::new(service)?;
+    let proto = match socktype {
+        SocketType::Stream => c"tcp".as_ptr(),
+        SocketType::Datagram => c"udp".as_ptr(),
+        // any protocol will do
+        SocketType::SeqPacket | SocketType::Raw | SocketType::Rdm => ptr::null(),
+    };
+    // SAFETY: the result points to static storage, we copy the port right away
+    let servent = unsafe { libc::getservbyname(name.as_ptr(), proto).as_ref() };
+    servent
+        // s_port is an int holding a port in network byte order
+        .map(|servent| u16::from_be(servent.s_port as u16))
+        .ok_or(ResolveError::Service)
+}
@@ -0,0 +1,435 @@
+//! Section 7.5 "Serialization—How to Pack Data"
+//!
+//! Like the book's `pack()` and `unpack()`, a format string says what goes in the buffer:
+//!
+//! | Code | Type  | Bytes                              |
+//! | ---- | ----- | ---------------------------------- |
+//! | `h`  | `i16` | 2                                  |
+//! | `H`  | `u16` | 2                                  |
+//! | `l`  | `i32` | 4                                  |
+//! | `L`  | `u32` | 4                                  |
+//! | `q`  | `i64` | 8                                  |
+//! | `Q`  | `u64` | 8                                  |
+//! | `f`  | `f32` | 4, IEEE 754                        |
+//! | `d`  | `f64` | 8, IEEE 754                        |
+//! | `s`  | `str` | 2 for the length, then UTF-8 bytes |
+//!
+//! Everything is in network byte order. A count before `s` (`"32s"`) is the longest string
+//! accepted. Whitespace in the format is ignored.
+//!
+//! In `pack2.c`, `f` and `d` are 16 and 32-bit floats: we keep the Rust sizes instead,
+//! so every value comes back exactly as it was packed.
+//!
+//! The book encodes them with `pack754()`. We send the native IEEE 754 bits in big-endian,
+//! `to_be_bytes`, which are the bytes [`crate::byteorder::pack754_32`] and
+//! [`crate::byteorder::pack754_64`] give for every value but NaN: those lose their payload
+//! there, and keep it here.
+//!
+//! [`Packer`] and [`Unpacker`] do the same without a format string.
+
+use std::{error, fmt};
+
+/// The longest string a 16-bit length can describe
+pub const MAX_STR_LEN: usize = u16::MAX as usize;
+
+/// One value of a packed buffer
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Str(String),
+}
+
+impl Value {
+    /// Its code in a format string
+    pub fn code(&self) -> char {
+        match self {
+            Value::I16(_) => 'h',
+            Value::U16(_) => 'H',
+            Value::I32(_) => 'l',
+            Value::U32(_) => 'L',
+            Value::I64(_) => 'q',
+            Value::U64(_) => 'Q',
+            Value::F32(_) => 'f',
+            Value::F64(_) => 'd',
+            Value::Str(_) => 's',
+        }
+    }
+
+    /// Parse `text` as the type of `code`, for values typed on the command line
+    pub fn parse(code: char, text: &str) -> Result<Value, PackError> {
+        let invalid = || PackError::InvalidValue {
+            code,
+            value: text.to_string(),
+        };
+        let value = match code {
+            'h' => Value::I16(text.parse().map_err(|_| invalid())?),
+            'H' => Value::U16(text.parse().map_err(|_| invalid())?),
+            'l' => Value::I32(text.parse().map_err(|_| invalid())?),
+            'L' => Value::U32(text.parse().map_err(|_| invalid())?),
+            'q' => Value::I64(text.parse().map_err(|_| invalid())?),
+            'Q' => Value::U64(text.parse().map_err(|_| invalid())?),
+            'f' => Value::F32(text.parse().map_err(|_| invalid())?),
+            'd' => Value::F64(text.parse().map_err(|_| invalid())?),
+            's' => Value::Str(text.to_string()),
+            _ => return Err(PackError::UnknownCode(code)),
+        };
+        Ok(value)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::I16(v) => write!(f, "{}", v),
+            Value::U16(v) => write!(f, "{}", v),
+            Value::I32(v) => write!(f, "{}", v),
+            Value::U32(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::U64(v) => write!(f, "{}", v),
+            Value::F32(v) => write!(f, "{}", v),
+            Value::F64(v) => write!(f, "{}", v),
+            Value::Str(v) => write!(f, "{:?}", v),
+        }
+    }
+}
+
+/// What can go wrong when packing or unpacking
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackError {
+    /// Not one of `hHlLqQfds`
+    UnknownCode(char),
+    /// A count before something else than `s`, or a count without a code
+    BadCount,
+    /// The value at `index` doesn't match the format
+    Mismatch { index: usize, expected: char },
+    /// More fields in the format than values
+    MissingValue { index: usize },
+    /// More values than fields in the format
+    ExtraValues { count: usize },
+    /// A string longer than its count, or than a 16-bit length
+    StrTooLong { len: usize, max: usize },
+    /// The buffer ended in the middle of a value
+    Truncated { needed: usize, left: usize },
+    /// Bytes left after the last field
+    TrailingBytes(usize),
+    /// A string that isn't UTF-8
+    InvalidUtf8,
+    /// Text that doesn't parse as the type of the code
+    InvalidValue { code: char, value: String },
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackError::UnknownCode(code) => write!(f, "unknown format code {:?}", code),
+            PackError::BadCount => write!(f, "a count is only allowed before 's'"),
+            PackError::Mismatch { index, expected } => {
+                write!(
+                    f,
+                    "value {} doesn't match format code {:?}",
+                    index, expected
+                )
+            }
+            PackError::MissingValue { index } => write!(f, "no value for field {}", index),
+            PackError::ExtraValues { count } => write!(f, "{} values more than the format", count),
+            PackError::StrTooLong { len, max } => {
+                write!(f, "string of {} bytes is longer than {}", len, max)
+            }
+            PackError::Truncated { needed, left } => {
+                write!(f, "need {} bytes, only {} left", needed, left)
+            }
+            PackError::TrailingBytes(len) => write!(f, "{} bytes left after the last field", len),
+            PackError::InvalidUtf8 => write!(f, "string is not UTF-8"),
+            PackError::InvalidValue { code, value } => {
+                write!(f, "{:?} is not a valid {:?}", value, code)
+            }
+        }
+    }
+}
+
+impl error::Error for PackError {}
+
+/// A parsed format string: the code, and the longest string for `s`
+fn parse_format(format: &str) -> Result<Vec<(char, usize)>, PackError> {
+    let mut fields = Vec::new();
+    let mut count: Option<usize> = None;
+    for c in format.chars().filter(|c| !c.is_whitespace()) {
+        if let Some(digit) = c.to_digit(10) {
+            let count = count.get_or_insert(0);
+            *count = count
+                .checked_mul(10)
+                .and_then(|count| count.checked_add(digit as usize))
+                .ok_or(PackError::BadCount)?;
+            continue;
+        }
+        if !"hHlLqQfds".contains(c) {
+            return Err(PackError::UnknownCode(c));
+        }
+        match (c, count.take()) {
+            ('s', max) => fields.push((c, max.unwrap_or(MAX_STR_LEN).min(MAX_STR_LEN))),
+            (_, None) => fields.push((c, 0)),
+            (_, Some(_)) => return Err(PackError::BadCount),
+        }
+    }
+    if count.is_some() {
+        return Err(PackError::BadCount);
+    }
+    Ok(fields)
+}
+
+/// Pack `values` as described by `format`
+pub fn pack(format: &str, values: &[Value]) -> Result<Vec<u8>, PackError> {
+    let fields = parse_format(format)?;
+    if values.len() > fields.len() {
+        return Err(PackError::ExtraValues {
+            count: values.len() - fields.len(),
+        });
+    }
+    let mut packer = Packer::new();
+    for (index, (code, max)) in fields.into_iter().enumerate() {
+        let value = values.get(index).ok_or(PackError::MissingValue { index })?;
+        if value.code() != code {
+            return Err(PackError::Mismatch {
+                index,
+                expected: code,
+            });
+        }
+        if let Value::Str(s) = value {
+            if s.len() > max {
+                return Err(PackError::StrTooLong { len: s.len(), max });
+            }
+        }
+        packer.value(value.clone());
+    }
+    packer.finish()
+}
+
+/// One value per field of `format`, parsed from `texts`
+pub fn parse_values<S: AsRef<str>>(format: &str, texts: &[S]) -> Result<Vec<Value>, PackError> {
+    let fields = parse_format(format)?;
+    if texts.len() > fields.len() {
+        return Err(PackError::ExtraValues {
+            count: texts.len() - fields.len(),
+        });
+    }
+    fields
+        .into_iter()
+        .enumerate()
+        .map(|(index, (code, _))| {
+            let text = texts.get(index).ok_or(PackError::MissingValue { index })?;
+            Value::parse(code, text.as_ref())
+        })
+        .collect()
+}
+
+/// Unpack a whole buffer as described by `format`
+pub fn unpack(format: &str, buf: &[u8]) -> Result<Vec<Value>, PackError> {
+    let mut unpacker = Unpacker::new(buf);
+    let mut values = Vec::new();
+    for (code, max) in parse_format(format)? {
+        let value = match code {
+            'h' => Value::I16(unpacker.i16()?),
+            'H' => Value::U16(unpacker.u16()?),
+            'l' => Value::I32(unpacker.i32()?),
+            'L' => Value::U32(unpacker.u32()?),
+            'q' => Value::I64(unpacker.i64()?),
+            'Q' => Value::U64(unpacker.u64()?),
+            'f' => Value::F32(unpacker.f32()?),
+            'd' => Value::F64(unpacker.f64()?),
+            _ => Value::Str(unpacker.str_max(max)?),
+        };
+        values.push(value);
+    }
+    unpacker.finish()?;
+    Ok(values)
+}
+
+/// Packs values one after the other, the typed version of [`pack`].
+///
+/// ```
+/// use beej_rs::serialize::{unpack, Packer, Value};
+///
+/// let buf = Packer::new().i16(-5).str("hi").f64(0.5).finish().unwrap();
+/// let values = unpack("hsd", &buf).unwrap();
+/// assert_eq!(values, [Value::I16(-5), Value::Str("hi".into()), Value::F64(0.5)]);
+/// ```
+#[derive(Debug, Default)]
+pub struct Packer {
+    buf: Vec<u8>,
+    // reported by finish, so the calls can be chained
+    error: Option<PackError>,
+}
+
+impl Packer {
+    pub fn new() -> Self {
+        Packer::default()
+    }
+
+    pub fn i16(&mut self, v: i16) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    pub fn u16(&mut self, v: u16) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    pub fn i32(&mut self, v: i32) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    pub fn u32(&mut self, v: u32) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    pub fn i64(&mut self, v: i64) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    pub fn u64(&mut self, v: u64) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    pub fn f32(&mut self, v: f32) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    pub fn f64(&mut self, v: f64) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    /// A 16-bit length then the bytes, longer strings make [`Packer::finish`] fail
+    pub fn str(&mut self, v: &str) -> &mut Self {
+        match u16::try_from(v.len()) {
+            Ok(len) => self.u16(len).bytes(v.as_bytes()),
+            Err(_) => {
+                self.error.get_or_insert(PackError::StrTooLong {
+                    len: v.len(),
+                    max: MAX_STR_LEN,
+                });
+                self
+            }
+        }
+    }
+
+    pub fn value(&mut self, value: Value) -> &mut Self {
+        match value {
+            Value::I16(v) => self.i16(v),
+            Value::U16(v) => self.u16(v),
+            Value::I32(v) => self.i32(v),
+            Value::U32(v) => self.u32(v),
+            Value::I64(v) => self.i64(v),
+            Value::U64(v) => self.u64(v),
+            Value::F32(v) => self.f32(v),
+            Value::F64(v) => self.f64(v),
+            Value::Str(v) => self.str(&v),
+        }
+    }
+
+    /// The packed buffer, or the first error
+    pub fn finish(&mut self) -> Result<Vec<u8>, PackError> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(std::mem::take(&mut self.buf)),
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+}
+
+/// Reads values one after the other, the typed version of [`unpack`]
+#[derive(Debug)]
+pub struct Unpacker<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Unpacker<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Unpacker { buf }
+    }
+
+    pub fn i16(&mut self) -> Result<i16, PackError> {
+        self.array().map(i16::from_be_bytes)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, PackError> {
+        self.array().map(u16::from_be_bytes)
+    }
+
+    pub fn i32(&mut self) -> Result<i32, PackError> {
+        self.array().map(i32::from_be_bytes)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, PackError> {
+        self.array().map(u32::from_be_bytes)
+    }
+
+    pub fn i64(&mut self) -> Result<i64, PackError> {
+        self.array().map(i64::from_be_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, PackError> {
+        self.array().map(u64::from_be_bytes)
+    }
+
+    pub fn f32(&mut self) -> Result<f32, PackError> {
+        self.array().map(f32::from_be_bytes)
+    }
+
+    pub fn f64(&mut self) -> Result<f64, PackError> {
+        self.array().map(f64::from_be_bytes)
+    }
+
+    pub fn str(&mut self) -> Result<String, PackError> {
+        self.str_max(MAX_STR_LEN)
+    }
+
+    /// A string of at most `max` bytes, like `"32s"`
+    pub fn str_max(&mut self, max: usize) -> Result<String, PackError> {
+        let len = self.u16()? as usize;
+        if len > max {
+            return Err(PackError::StrTooLong { len, max });
+        }
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| PackError::InvalidUtf8)
+    }
+
+    /// Bytes not read yet
+    pub fn remaining(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Make sure the whole buffer was read
+    pub fn finish(&self) -> Result<(), PackError> {
+        match self.buf.len() {
+            0 => Ok(()),
+            len => Err(PackError::TrailingBytes(len)),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PackError> {
+        if self.buf.len() < len {
+            return Err(PackError::Truncated {
+                needed: len,
+                left: self.buf.len(),
+            });
+        }
+        let (taken, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], PackError> {
+        self.take(N)
+            .map(|bytes| bytes.try_into().expect("took N bytes"))
+    }
+}
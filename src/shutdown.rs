@@ -0,0 +1,191 @@
+//! Graceful shutdown of the long-running servers, with the self-pipe trick.
+//!
+//! The `SIGINT` and `SIGTERM` handler writes a byte to a pipe, so a server waiting
+//! in `poll` or `select` only has to watch one more fd to notice it's time to stop.
+
+use std::{
+    io,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    sync::atomic::{AtomicI32, Ordering},
+    time::{Duration, Instant},
+};
+
+use nix::{
+    errno::Errno,
+    poll::{PollFd, PollFlags, PollTimeout},
+    sys::{
+        signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal},
+        socket::{self, MsgFlags},
+    },
+};
+
+use crate::partial;
+
+/// The signals asking us to stop
+pub const SIGNALS: [Signal; 2] = [Signal::SIGINT, Signal::SIGTERM];
+
+/// Default message for the clients still connected when we stop
+pub const GOODBYE: &str = "Server is shutting down, bye!\n";
+
+/// How long [`goodbye`] waits for the clients to close their side
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Write end of the pipe, for the signal handler
+static PIPE_WRITER: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn shutdown_handler(_: libc::c_int) {
+    // write() might overwrite errno, so we save and restore it
+    let saved_errno = Errno::last_raw();
+    let fd = PIPE_WRITER.load(Ordering::SeqCst);
+    if fd != -1 {
+        // when the pipe is full a shutdown is already pending, so errors don't matter
+        unsafe { libc::write(fd, [1u8].as_ptr() as *const libc::c_void, 1) };
+    }
+    Errno::set_raw(saved_errno);
+}
+
+/// What [`Shutdown::wait`] woke up for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The fd is ready to read
+    Readable,
+    /// A signal asked us to stop
+    Shutdown,
+}
+
+/// Becomes readable once `SIGINT` or `SIGTERM` is received.
+///
+/// Only one should be alive at a time: dropping it puts back the default handlers.
+#[derive(Debug)]
+pub struct Shutdown {
+    reader: OwnedFd,
+    // kept open for the handler
+    _writer: OwnedFd,
+}
+
+impl Shutdown {
+    /// Create the pipe and install the signal handlers
+    pub fn install() -> io::Result<Self> {
+        let mut fds = [0; 2];
+        // non-blocking, so the handler can't get stuck on a full pipe
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the fds were just created and nobody else owns them
+        let (reader, writer) =
+            unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        PIPE_WRITER.store(writer.as_raw_fd(), Ordering::SeqCst);
+
+        // poll() and select() are interrupted anyway, SA_RESTART keeps the other calls going
+        let sa = SigAction::new(
+            SigHandler::Handler(shutdown_handler),
+            SaFlags::SA_RESTART,
+            SigSet::empty(),
+        );
+        for signal in SIGNALS {
+            unsafe { signal::sigaction(signal, &sa) }?;
+        }
+        Ok(Shutdown {
+            reader,
+            _writer: writer,
+        })
+    }
+
+    /// Check without blocking
+    pub fn requested(&self) -> bool {
+        let mut pfds = [PollFd::new(self.reader.as_fd(), PollFlags::POLLIN)];
+        matches!(nix::poll::poll(&mut pfds, PollTimeout::ZERO), Ok(n) if n > 0)
+    }
+
+    /// Block until `fd` is ready to read, or until we are asked to stop
+    pub fn wait<Fd: AsFd>(&self, fd: Fd) -> io::Result<Event> {
+        loop {
+            let mut pfds = [
+                PollFd::new(self.reader.as_fd(), PollFlags::POLLIN),
+                PollFd::new(fd.as_fd(), PollFlags::POLLIN),
+            ];
+            match nix::poll::poll(&mut pfds, PollTimeout::NONE) {
+                Ok(_) => {}
+                Err(Errno::EINTR) => continue,
+                Err(e) => return Err(e.into()),
+            }
+            // stopping wins over a last request
+            if pfds[0].any().unwrap_or_default() {
+                return Ok(Event::Shutdown);
+            }
+            if pfds[1].any().unwrap_or_default() {
+                return Ok(Event::Readable);
+            }
+        }
+    }
+}
+
+impl AsFd for Shutdown {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.reader.as_fd()
+    }
+}
+
+impl Drop for Shutdown {
+    fn drop(&mut self) {
+        PIPE_WRITER.store(-1, Ordering::SeqCst);
+        let sa = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+        for signal in SIGNALS {
+            let _ = unsafe { signal::sigaction(signal, &sa) };
+        }
+    }
+}
+
+/// Send `message` to every client, then close them once they are done.
+///
+/// Our side is shut down right after the message, so the clients read it followed by EOF.
+/// We keep reading (and dropping) what they still send until they close, or
+/// until `timeout`: closing a socket with unread data makes the kernel reset the
+/// connection, and the goodbye could be lost.
+pub fn goodbye(clients: Vec<OwnedFd>, message: &[u8], timeout: Duration) {
+    let mut clients: Vec<OwnedFd> = clients
+        .into_iter()
+        .filter(|client| {
+            // the client is already gone when it fails
+            partial::sendall(client, message).is_ok()
+                && socket::shutdown(client.as_raw_fd(), socket::Shutdown::Write).is_ok()
+        })
+        .collect();
+
+    let deadline = Instant::now() + timeout;
+    while !clients.is_empty() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        let mut pfds: Vec<PollFd> = clients
+            .iter()
+            .map(|client| PollFd::new(client.as_fd(), PollFlags::POLLIN))
+            .collect();
+        let timeout = PollTimeout::try_from(left).unwrap_or(PollTimeout::MAX);
+        match nix::poll::poll(&mut pfds, timeout) {
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(_) => break,
+        }
+        let done: Vec<bool> = pfds
+            .iter()
+            .zip(&clients)
+            .map(|(pfd, client)| {
+                if !pfd.any().unwrap_or_default() {
+                    return false;
+                }
+                let mut buf = [0u8; 1024];
+                // EOF or an error, either way we are done with this one
+                !matches!(
+                    socket::recv(client.as_raw_fd(), &mut buf, MsgFlags::MSG_DONTWAIT),
+                    Ok(n) if n > 0
+                )
+            })
+            .collect();
+        drop(pfds);
+
+        let mut done = done.into_iter();
+        // the clients that are done are closed when dropped
+        clients.retain(|_| !done.next().unwrap_or_default());
+    }
+}
@@ -0,0 +1,303 @@
+//! An owned socket that closes itself, so the examples never call `close(2)` by hand.
+
+use std::{
+    io, mem,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
+    time::{Duration, Instant},
+};
+
+use nix::{
+    errno::Errno,
+    poll::{PollFd, PollFlags, PollTimeout},
+};
+use socket2::SockAddr;
+
+use crate::types::{AddressFamily, Protocol, SocketType};
+
+/// Turn the `-1` returned by a libc call into the `errno` it set
+fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Same as [`check`], for the calls returning a size
+fn check_len(ret: libc::ssize_t) -> io::Result<usize> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+/// A socket file descriptor, closed when dropped.
+///
+/// The methods are thin wrappers around the libc calls of the same name,
+/// returning the `errno` as an [`io::Error`].
+#[derive(Debug)]
+pub struct Socket {
+    fd: OwnedFd,
+}
+
+impl Socket {
+    /// `socket(2)`, the protocol is picked by the kernel when it's `None`
+    pub fn new(
+        family: AddressFamily,
+        socktype: SocketType,
+        protocol: Option<Protocol>,
+    ) -> io::Result<Self> {
+        Socket::from_args(
+            family.into(),
+            socktype.into(),
+            protocol.map_or(0, Into::into),
+        )
+    }
+
+    /// `socket(2)` with raw values, like the ones from
+    /// [`AddrInfoEntry::socket_args`](crate::resolver::AddrInfoEntry::socket_args)
+    pub fn from_args(
+        family: libc::c_int,
+        socktype: libc::c_int,
+        protocol: libc::c_int,
+    ) -> io::Result<Self> {
+        // close-on-exec, so children started with exec don't inherit it
+        let fd = check(unsafe { libc::socket(family, socktype | libc::SOCK_CLOEXEC, protocol) })?;
+        // SAFETY: the fd was just created and nobody else owns it
+        Ok(Socket {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// `SO_REUSEADDR`, to bind again right after a restart
+    pub fn set_reuseaddr(&self, reuse: bool) -> io::Result<()> {
+        let optval = libc::c_int::from(reuse);
+        check(unsafe {
+            libc::setsockopt(
+                self.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_REUSEADDR,
+                &optval as *const _ as *const libc::c_void,
+                mem::size_of_val(&optval) as libc::socklen_t,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// `SO_RCVTIMEO`, [`Socket::recv`] fails with `EAGAIN` when nothing arrives in time.
+    ///
+    /// `None` waits forever.
+    pub fn set_recv_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        let optval = match timeout {
+            Some(timeout) => libc::timeval {
+                tv_sec: timeout.as_secs() as libc::time_t,
+                tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+            },
+            None => libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+        };
+        check(unsafe {
+            libc::setsockopt(
+                self.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &optval as *const _ as *const libc::c_void,
+                mem::size_of_val(&optval) as libc::socklen_t,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// `O_NONBLOCK`, calls that would block fail with `EAGAIN` (or `EINPROGRESS` for `connect`)
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        let flags = check(unsafe { libc::fcntl(self.as_raw_fd(), libc::F_GETFL) })?;
+        let flags = if nonblocking {
+            flags | libc::O_NONBLOCK
+        } else {
+            flags & !libc::O_NONBLOCK
+        };
+        check(unsafe { libc::fcntl(self.as_raw_fd(), libc::F_SETFL, flags) })?;
+        Ok(())
+    }
+
+    /// `SO_ERROR`, the pending error of the socket, cleared by reading it
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        let mut optval: libc::c_int = 0;
+        let mut optlen = mem::size_of_val(&optval) as libc::socklen_t;
+        check(unsafe {
+            libc::getsockopt(
+                self.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_ERROR,
+                &mut optval as *mut _ as *mut libc::c_void,
+                &mut optlen,
+            )
+        })?;
+        Ok((optval != 0).then(|| io::Error::from_raw_os_error(optval)))
+    }
+
+    pub fn bind(&self, addr: &SockAddr) -> io::Result<()> {
+        check(unsafe { libc::bind(self.as_raw_fd(), addr.as_ptr(), addr.len()) })?;
+        Ok(())
+    }
+
+    /// `backlog` is how many pending connections the queue will hold
+    pub fn listen(&self, backlog: libc::c_int) -> io::Result<()> {
+        check(unsafe { libc::listen(self.as_raw_fd(), backlog) })?;
+        Ok(())
+    }
+
+    /// Wait for a connection, returning its socket and the address of the peer
+    pub fn accept(&self) -> io::Result<(Socket, SockAddr)> {
+        // SAFETY: accept4 fills the storage and its length
+        let (fd, addr) = unsafe {
+            SockAddr::try_init(|storage, len| {
+                check(libc::accept4(
+                    self.as_raw_fd(),
+                    storage as *mut libc::sockaddr,
+                    len,
+                    libc::SOCK_CLOEXEC,
+                ))
+            })
+        }?;
+        // SAFETY: the fd was just created and nobody else owns it
+        let socket = Socket {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        };
+        Ok((socket, addr))
+    }
+
+    pub fn connect(&self, addr: &SockAddr) -> io::Result<()> {
+        check(unsafe { libc::connect(self.as_raw_fd(), addr.as_ptr(), addr.len()) })?;
+        Ok(())
+    }
+
+    /// Connect, giving up with `ETIMEDOUT` after `timeout`, like the FAQ
+    /// "How can I implement a timeout on a call to connect()?".
+    ///
+    /// The connect is started non-blocking, we `poll` until the socket is writable,
+    /// then `SO_ERROR` tells how it went. The socket is back in blocking mode afterwards.
+    pub fn connect_timeout(&self, addr: &SockAddr, timeout: Duration) -> io::Result<()> {
+        self.set_nonblocking(true)?;
+        let result = self.finish_connect(addr, timeout);
+        self.set_nonblocking(false)?;
+        result
+    }
+
+    fn finish_connect(&self, addr: &SockAddr, timeout: Duration) -> io::Result<()> {
+        match self.connect(addr) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
+            Err(e) => return Err(e),
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(io::Error::from_raw_os_error(libc::ETIMEDOUT));
+            }
+            let mut pfds = [PollFd::new(self.as_fd(), PollFlags::POLLOUT)];
+            match nix::poll::poll(
+                &mut pfds,
+                PollTimeout::try_from(left).unwrap_or(PollTimeout::MAX),
+            ) {
+                Ok(0) | Err(Errno::EINTR) => continue,
+                Ok(_) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match self.take_error()? {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Send some of `buf`, returning how much was sent.
+    ///
+    /// A closed peer is reported as `EPIPE` instead of killing us with `SIGPIPE`.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        check_len(unsafe {
+            libc::send(
+                self.as_raw_fd(),
+                buf.as_ptr() as *const libc::c_void,
+                buf.len(),
+                libc::MSG_NOSIGNAL,
+            )
+        })
+    }
+
+    /// Receive into `buf`, returning how much was received, 0 once the peer is done
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        check_len(unsafe {
+            libc::recv(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        })
+    }
+
+    /// Address the socket is bound to
+    pub fn local_addr(&self) -> io::Result<SockAddr> {
+        // SAFETY: getsockname fills the storage and its length
+        let ((), addr) = unsafe {
+            SockAddr::try_init(|storage, len| {
+                check(libc::getsockname(
+                    self.as_raw_fd(),
+                    storage as *mut libc::sockaddr,
+                    len,
+                ))
+                .map(drop)
+            })
+        }?;
+        Ok(addr)
+    }
+}
+
+impl AsFd for Socket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for Socket {
+    fn into_raw_fd(self) -> RawFd {
+        self.fd.into_raw_fd()
+    }
+}
+
+/// nix hands out sockets as [`OwnedFd`]
+impl From<OwnedFd> for Socket {
+    fn from(fd: OwnedFd) -> Self {
+        Socket { fd }
+    }
+}
+
+impl From<Socket> for OwnedFd {
+    fn from(socket: Socket) -> Self {
+        socket.fd
+    }
+}
+
+impl From<socket2::Socket> for Socket {
+    fn from(socket: socket2::Socket) -> Self {
+        Socket { fd: socket.into() }
+    }
+}
+
+impl From<Socket> for socket2::Socket {
+    fn from(socket: Socket) -> Self {
+        socket.fd.into()
+    }
+}
@@ -1,6 +1,7 @@
 
 use std::fmt::Display;
 
+use bitflags::bitflags;
 use clap::ValueEnum;
 
 #[derive(Debug, Clone, PartialEq, ValueEnum)]
@@ -20,9 +21,9 @@ impl Display for Family {
     }
 }
 
-impl Into<libc::c_int> for Family {
-    fn into(self) -> libc::c_int {
-        match self {
+impl From<Family> for libc::c_int {
+    fn from(family: Family) -> Self {
+        match family {
             Family::Ipv4 => libc::AF_INET,
             Family::Ipv6 => libc::AF_INET6,
             Family::Unspecified => libc::AF_UNSPEC,
@@ -40,61 +41,452 @@ impl From<libc::c_int> for Family {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A libc constant that has no matching variant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnknownConstant {
+    /// What we were converting, like "socket type"
+    pub kind: &'static str,
+    pub value: libc::c_int,
+}
+
+impl Display for UnknownConstant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown {}: {}", self.kind, self.value)
+    }
+}
+
+impl std::error::Error for UnknownConstant {}
+
+/// Every address family `socket(2)` can be opened with, unlike [`Family`]
+/// which only covers what `getaddrinfo` deals with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    Unspecified,
+    /// IPv4
+    Inet,
+    /// IPv6
+    Inet6,
+    /// Local communication, also known as `AF_LOCAL`
+    Unix,
+    /// Raw packets at the device driver level
+    Packet,
+}
+
+impl From<AddressFamily> for libc::c_int {
+    fn from(family: AddressFamily) -> Self {
+        match family {
+            AddressFamily::Unspecified => libc::AF_UNSPEC,
+            AddressFamily::Inet => libc::AF_INET,
+            AddressFamily::Inet6 => libc::AF_INET6,
+            AddressFamily::Unix => libc::AF_UNIX,
+            AddressFamily::Packet => libc::AF_PACKET,
+        }
+    }
+}
+
+impl TryFrom<libc::c_int> for AddressFamily {
+    type Error = UnknownConstant;
+
+    fn try_from(family: libc::c_int) -> Result<Self, Self::Error> {
+        match family {
+            libc::AF_UNSPEC => Ok(AddressFamily::Unspecified),
+            libc::AF_INET => Ok(AddressFamily::Inet),
+            libc::AF_INET6 => Ok(AddressFamily::Inet6),
+            libc::AF_UNIX => Ok(AddressFamily::Unix),
+            libc::AF_PACKET => Ok(AddressFamily::Packet),
+            value => Err(UnknownConstant {
+                kind: "address family",
+                value,
+            }),
+        }
+    }
+}
+
+impl From<AddressFamily> for nix::sys::socket::AddressFamily {
+    fn from(family: AddressFamily) -> Self {
+        match family {
+            AddressFamily::Unspecified => nix::sys::socket::AddressFamily::Unspec,
+            AddressFamily::Inet => nix::sys::socket::AddressFamily::Inet,
+            AddressFamily::Inet6 => nix::sys::socket::AddressFamily::Inet6,
+            AddressFamily::Unix => nix::sys::socket::AddressFamily::Unix,
+            AddressFamily::Packet => nix::sys::socket::AddressFamily::Packet,
+        }
+    }
+}
+
+impl TryFrom<nix::sys::socket::AddressFamily> for AddressFamily {
+    type Error = UnknownConstant;
+
+    fn try_from(family: nix::sys::socket::AddressFamily) -> Result<Self, Self::Error> {
+        (family as libc::c_int).try_into()
+    }
+}
+
+impl From<Family> for AddressFamily {
+    fn from(family: Family) -> Self {
+        match family {
+            Family::Ipv4 => AddressFamily::Inet,
+            Family::Ipv6 => AddressFamily::Inet6,
+            Family::Unspecified => AddressFamily::Unspecified,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
 pub enum SocketType {
+    /// Reliable two way byte streams, like TCP
     Stream,
+    /// Connectionless unreliable messages, like UDP
     Datagram,
+    /// Reliable two way messages with boundaries, like SCTP
+    #[value(name = "seqpacket")]
+    SeqPacket,
+    /// Direct access to the network protocol, usually needs root
+    Raw,
+    /// Reliable messages without ordering guarantees
+    Rdm,
 }
 
-impl Into<libc::c_int> for SocketType {
-    fn into(self) -> libc::c_int {
+impl Display for SocketType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            SocketType::Stream => write!(f, "stream"),
+            SocketType::Datagram => write!(f, "datagram"),
+            SocketType::SeqPacket => write!(f, "seqpacket"),
+            SocketType::Raw => write!(f, "raw"),
+            SocketType::Rdm => write!(f, "rdm"),
+        }
+    }
+}
+
+impl From<SocketType> for libc::c_int {
+    fn from(socktype: SocketType) -> Self {
+        match socktype {
             SocketType::Stream => libc::SOCK_STREAM,
             SocketType::Datagram => libc::SOCK_DGRAM,
+            SocketType::SeqPacket => libc::SOCK_SEQPACKET,
+            SocketType::Raw => libc::SOCK_RAW,
+            SocketType::Rdm => libc::SOCK_RDM,
         }
     }
 }
 
-impl From<libc::c_int> for SocketType {
-    fn from(socktype: libc::c_int) -> Self {
+impl TryFrom<libc::c_int> for SocketType {
+    type Error = UnknownConstant;
+
+    fn try_from(socktype: libc::c_int) -> Result<Self, Self::Error> {
         match socktype {
-            libc::SOCK_STREAM => SocketType::Stream,
-            libc::SOCK_DGRAM => SocketType::Datagram,
-            _ => panic!("Unknown socket type"),
+            libc::SOCK_STREAM => Ok(SocketType::Stream),
+            libc::SOCK_DGRAM => Ok(SocketType::Datagram),
+            libc::SOCK_SEQPACKET => Ok(SocketType::SeqPacket),
+            libc::SOCK_RAW => Ok(SocketType::Raw),
+            libc::SOCK_RDM => Ok(SocketType::Rdm),
+            value => Err(UnknownConstant {
+                kind: "socket type",
+                value,
+            }),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Flag {
-    /// No flags
-    None,
+impl From<SocketType> for nix::sys::socket::SockType {
+    fn from(socktype: SocketType) -> Self {
+        match socktype {
+            SocketType::Stream => nix::sys::socket::SockType::Stream,
+            SocketType::Datagram => nix::sys::socket::SockType::Datagram,
+            SocketType::SeqPacket => nix::sys::socket::SockType::SeqPacket,
+            SocketType::Raw => nix::sys::socket::SockType::Raw,
+            SocketType::Rdm => nix::sys::socket::SockType::Rdm,
+        }
+    }
+}
 
-    /// assign the address of my local host to the socket structures
-    Passive,
+impl TryFrom<nix::sys::socket::SockType> for SocketType {
+    type Error = UnknownConstant;
+
+    fn try_from(socktype: nix::sys::socket::SockType) -> Result<Self, Self::Error> {
+        (socktype as libc::c_int).try_into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    /// Stream Control Transmission Protocol, message oriented and reliable
+    Sctp,
+    Icmp,
+    IcmpV6,
 }
 
-impl Into<libc::c_int> for Flag {
-    fn into(self) -> libc::c_int {
+impl Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Flag::None => 0,
-            Flag::Passive => libc::AI_PASSIVE,
+            Protocol::Tcp => write!(f, "tcp"),
+            Protocol::Udp => write!(f, "udp"),
+            Protocol::Sctp => write!(f, "sctp"),
+            Protocol::Icmp => write!(f, "icmp"),
+            Protocol::IcmpV6 => write!(f, "icmpv6"),
         }
     }
 }
 
+impl From<Protocol> for libc::c_int {
+    fn from(protocol: Protocol) -> Self {
+        match protocol {
+            Protocol::Tcp => libc::IPPROTO_TCP,
+            Protocol::Udp => libc::IPPROTO_UDP,
+            Protocol::Sctp => libc::IPPROTO_SCTP,
+            Protocol::Icmp => libc::IPPROTO_ICMP,
+            Protocol::IcmpV6 => libc::IPPROTO_ICMPV6,
+        }
+    }
+}
+
+impl TryFrom<libc::c_int> for Protocol {
+    type Error = UnknownConstant;
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum SockFd {
-    Empty,
-    Initialized(i32),
+    fn try_from(protocol: libc::c_int) -> Result<Self, Self::Error> {
+        match protocol {
+            libc::IPPROTO_TCP => Ok(Protocol::Tcp),
+            libc::IPPROTO_UDP => Ok(Protocol::Udp),
+            libc::IPPROTO_SCTP => Ok(Protocol::Sctp),
+            libc::IPPROTO_ICMP => Ok(Protocol::Icmp),
+            libc::IPPROTO_ICMPV6 => Ok(Protocol::IcmpV6),
+            value => Err(UnknownConstant {
+                kind: "protocol",
+                value,
+            }),
+        }
+    }
 }
 
-impl Into<i32> for SockFd {
-    fn into(self) -> i32 {
+/// nix has no SCTP variant, so this conversion can fail
+impl TryFrom<Protocol> for nix::sys::socket::SockProtocol {
+    type Error = UnknownConstant;
+
+    fn try_from(protocol: Protocol) -> Result<Self, Self::Error> {
+        match protocol {
+            Protocol::Tcp => Ok(nix::sys::socket::SockProtocol::Tcp),
+            Protocol::Udp => Ok(nix::sys::socket::SockProtocol::Udp),
+            Protocol::Icmp => Ok(nix::sys::socket::SockProtocol::Icmp),
+            Protocol::IcmpV6 => Ok(nix::sys::socket::SockProtocol::IcmpV6),
+            Protocol::Sctp => Err(UnknownConstant {
+                kind: "nix protocol",
+                value: libc::IPPROTO_SCTP,
+            }),
+        }
+    }
+}
+
+impl TryFrom<nix::sys::socket::SockProtocol> for Protocol {
+    type Error = UnknownConstant;
+
+    fn try_from(protocol: nix::sys::socket::SockProtocol) -> Result<Self, Self::Error> {
+        (protocol as libc::c_int).try_into()
+    }
+}
+
+/// How to print the results of a lookup
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable, tab indented
+    Text,
+    /// A JSON array, one object per result
+    Json,
+    /// A header line, then one line per result
+    Csv,
+}
+
+/// Who does the name resolution
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ResolverKind {
+    /// glibc, through `getaddrinfo`
+    System,
+    /// Our own DNS stub resolver, see [`crate::dns`]
+    Builtin,
+}
+
+/// How the stream server deals with its connections
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ServerMode {
+    /// One connection after the other
+    Iterative,
+    /// A child process per connection, like the book does
+    Fork,
+    /// A thread per connection
+    Thread,
+    /// A fixed number of threads, fed by a bounded queue
+    Pool,
+}
+
+/// How the chat servers cut the stream of a connection into messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Framing {
+    /// Whatever one `recv` returns, like the book, in pieces of at most the maximum size
+    Raw,
+    /// Lines ending with a newline
+    Line,
+    /// A 2-byte length in network byte order, then the message
+    U16,
+    /// A 4-byte length in network byte order, then the message
+    U32,
+}
+
+/// Which system call the chat servers wait with, see [`crate::reactor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// `poll()`, section 7.2
+    Poll,
+    /// `select()`, section 7.3, limited to fds below `FD_SETSIZE`
+    Select,
+    /// `epoll`, Linux only, the cost doesn't grow with the number of clients
+    Epoll,
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            SockFd::Empty => -1,
-            SockFd::Initialized(fd) => fd,
+            Backend::Poll => write!(f, "poll"),
+            Backend::Select => write!(f, "select"),
+            Backend::Epoll => write!(f, "epoll"),
+        }
+    }
+}
+
+/// A single `getaddrinfo` hint, combine them into [`Flags`]
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Flag {
+    /// assign the address of my local host to the socket structures
+    Passive,
+
+    /// return the canonical name of the host in the first result
+    #[value(name = "canonname")]
+    CanonName,
+
+    /// the host must be a numeric address, don't do any lookup
+    #[value(name = "numerichost")]
+    NumericHost,
+
+    /// return IPv4-mapped IPv6 addresses if no IPv6 address is found
+    #[value(name = "v4mapped")]
+    V4Mapped,
+
+    /// with `v4mapped`, return both IPv6 and IPv4-mapped addresses
+    All,
+
+    /// only return families configured on the local system
+    #[value(name = "addrconfig")]
+    AddrConfig,
+
+    /// the service must be a numeric port, don't do any lookup
+    #[value(name = "numericserv")]
+    NumericServ,
+
+    /// convert the host to IDN format before the lookup (glibc extension)
+    Idn,
+
+    /// return the canonical name in the current locale (glibc extension)
+    #[value(name = "canonidn")]
+    CanonIdn,
+}
+
+bitflags! {
+    /// Set of hints for `ai_flags`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Flags: libc::c_int {
+        const PASSIVE = libc::AI_PASSIVE;
+        const CANONNAME = libc::AI_CANONNAME;
+        const NUMERICHOST = libc::AI_NUMERICHOST;
+        const V4MAPPED = libc::AI_V4MAPPED;
+        const ALL = libc::AI_ALL;
+        const ADDRCONFIG = libc::AI_ADDRCONFIG;
+        const NUMERICSERV = libc::AI_NUMERICSERV;
+        // glibc extensions, not exposed by the libc crate
+        const IDN = 0x0040;
+        const CANONIDN = 0x0080;
+    }
+}
+
+impl From<Flag> for Flags {
+    fn from(flag: Flag) -> Self {
+        match flag {
+            Flag::Passive => Flags::PASSIVE,
+            Flag::CanonName => Flags::CANONNAME,
+            Flag::NumericHost => Flags::NUMERICHOST,
+            Flag::V4Mapped => Flags::V4MAPPED,
+            Flag::All => Flags::ALL,
+            Flag::AddrConfig => Flags::ADDRCONFIG,
+            Flag::NumericServ => Flags::NUMERICSERV,
+            Flag::Idn => Flags::IDN,
+            Flag::CanonIdn => Flags::CANONIDN,
         }
     }
-}
\ No newline at end of file
+}
+
+impl FromIterator<Flag> for Flags {
+    fn from_iter<T: IntoIterator<Item = Flag>>(iter: T) -> Self {
+        iter.into_iter()
+            .fold(Flags::empty(), |flags, flag| flags | flag.into())
+    }
+}
+
+impl From<Flags> for libc::c_int {
+    fn from(flags: Flags) -> Self {
+        flags.bits()
+    }
+}
+
+/// A single `getnameinfo` flag, combine them into [`NameInfoFlags`]
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum NameInfoFlag {
+    /// fail if the host name can't be found, instead of returning the numeric address
+    #[value(name = "namereqd")]
+    NameReqd,
+
+    /// return the numeric form of the host address
+    #[value(name = "numerichost")]
+    NumericHost,
+
+    /// return the numeric form of the port
+    #[value(name = "numericserv")]
+    NumericServ,
+
+    /// the service is datagram based (UDP) rather than stream based (TCP)
+    Dgram,
+
+    /// return only the hostname part of the FQDN for local hosts
+    #[value(name = "nofqdn")]
+    NoFqdn,
+}
+
+bitflags! {
+    /// Set of flags for `getnameinfo`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct NameInfoFlags: libc::c_int {
+        const NAMEREQD = libc::NI_NAMEREQD;
+        const NUMERICHOST = libc::NI_NUMERICHOST;
+        const NUMERICSERV = libc::NI_NUMERICSERV;
+        const DGRAM = libc::NI_DGRAM;
+        const NOFQDN = libc::NI_NOFQDN;
+    }
+}
+
+impl From<NameInfoFlag> for NameInfoFlags {
+    fn from(flag: NameInfoFlag) -> Self {
+        match flag {
+            NameInfoFlag::NameReqd => NameInfoFlags::NAMEREQD,
+            NameInfoFlag::NumericHost => NameInfoFlags::NUMERICHOST,
+            NameInfoFlag::NumericServ => NameInfoFlags::NUMERICSERV,
+            NameInfoFlag::Dgram => NameInfoFlags::DGRAM,
+            NameInfoFlag::NoFqdn => NameInfoFlags::NOFQDN,
+        }
+    }
+}
+
+impl FromIterator<NameInfoFlag> for NameInfoFlags {
+    fn from_iter<T: IntoIterator<Item = NameInfoFlag>>(iter: T) -> Self {
+        iter.into_iter()
+            .fold(NameInfoFlags::empty(), |flags, flag| flags | flag.into())
+    }
+}
@@ -0,0 +1,147 @@
+use std::{net::Ipv4Addr, process::Command};
+
+use beej_rs::interfaces::{directed_broadcast, ipv4_interface, ipv4_interfaces};
+
+mod common;
+use common::Server;
+
+fn listener(port: &str) -> Server {
+    Server::start_when(
+        &["broadcast-listener", "--port", port],
+        &format!("Listening for broadcasts on 0.0.0.0:{}\n", port),
+    )
+}
+
+fn stop(server: Server) {
+    let (status, _) = server.stop();
+    assert!(status.success(), "{}", status);
+}
+
+#[test]
+fn broadcast_addresses() {
+    let cases = [
+        ("192.168.1.42", "255.255.255.0", "192.168.1.255"),
+        ("10.1.2.3", "255.0.0.0", "10.255.255.255"),
+        ("172.16.5.4", "255.255.240.0", "172.16.15.255"),
+        ("192.0.2.1", "255.255.255.255", "192.0.2.1"),
+        ("0.0.0.0", "0.0.0.0", "255.255.255.255"),
+    ];
+    for (addr, netmask, broadcast) in cases {
+        let addr: Ipv4Addr = addr.parse().unwrap();
+        let netmask: Ipv4Addr = netmask.parse().unwrap();
+        assert_eq!(
+            directed_broadcast(addr, netmask),
+            broadcast.parse::<Ipv4Addr>().unwrap()
+        );
+    }
+}
+
+#[test]
+fn loopback_interface() {
+    let lo = ipv4_interfaces()
+        .unwrap()
+        .into_iter()
+        .find(|i| i.addr == Ipv4Addr::LOCALHOST)
+        .expect("no loopback interface");
+    assert_eq!(lo.netmask, Ipv4Addr::new(255, 0, 0, 0));
+    assert_eq!(lo.broadcast(), Ipv4Addr::new(127, 255, 255, 255));
+    // the loopback has no IFF_BROADCAST, --all-interfaces leaves it out
+    assert!(!lo.can_broadcast);
+    assert_eq!(ipv4_interface(&lo.name).unwrap(), Some(lo));
+    assert_eq!(ipv4_interface("no-such-interface").unwrap(), None);
+}
+
+#[test]
+fn broadcaster_to_listeners() {
+    let lo = ipv4_interfaces()
+        .unwrap()
+        .into_iter()
+        .find(|i| i.addr == Ipv4Addr::LOCALHOST)
+        .expect("no loopback interface");
+    // SO_REUSEADDR lets both bind the port, and both get the broadcast
+    let first = listener("9142");
+    let second = listener("9142");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["broadcaster", "--port", "9142", "--interface", &lo.name])
+        .arg("Hello, World!")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Sending message to 127.255.255.255:9142\n"
+    );
+
+    for listener in [&first, &second] {
+        let line = listener.next_line();
+        assert!(line.starts_with("got packet from 127.0.0.1:"), "{}", line);
+        assert!(line.ends_with(": \"Hello, World!\""), "{}", line);
+    }
+    stop(first);
+    stop(second);
+}
+
+#[test]
+fn all_interfaces_skips_loopback() {
+    let broadcasts: Vec<String> = ipv4_interfaces()
+        .unwrap()
+        .iter()
+        .filter(|i| i.can_broadcast)
+        .map(|i| format!("Sending message to {}:9143\n", i.broadcast()))
+        .collect();
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["broadcaster", "--port", "9143", "--all-interfaces", "hi"])
+        .output()
+        .unwrap();
+    if broadcasts.is_empty() {
+        assert_eq!(output.status.code(), Some(2));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "broadcaster: no interface with a broadcast address\n"
+        );
+    } else {
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), broadcasts.concat());
+    }
+}
+
+#[test]
+fn unknown_interface() {
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["broadcaster", "--interface", "no-such-interface", "hi"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "broadcaster: no IPv4 interface named no-such-interface\n"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args([
+            "broadcaster",
+            "--host",
+            "127.0.0.1",
+            "--all-interfaces",
+            "hi",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn failed_send_is_reported() {
+    // nobody can send to port 0
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["broadcaster", "--host", "127.0.0.1", "--port", "0", "hi"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "broadcaster: sendto 127.0.0.1:0: EINVAL: Invalid argument\n"
+    );
+}
@@ -0,0 +1,135 @@
+use std::process::Command;
+
+use beej_rs::byteorder::{
+    htonll, ntohll, pack754, pack754_32, pack754_64, pack754_checked, unpack754, unpack754_32,
+    unpack754_64,
+};
+use proptest::prelude::*;
+
+/// The floats the book's version gets wrong
+const SPECIALS: [f64; 10] = [
+    0.0,
+    -0.0,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+    f64::MIN_POSITIVE,
+    f64::MAX,
+    f64::MIN,
+    5e-324,                 // smallest subnormal
+    2.225073858507201e-308, // largest subnormal
+    -1.5e-310,
+];
+
+#[test]
+fn specials_match_native_doubles() {
+    for f in SPECIALS {
+        assert_eq!(pack754_checked(f), Ok(f.to_bits()), "{:?}", f);
+        assert_eq!(unpack754_64(f.to_bits()).to_bits(), f.to_bits(), "{:?}", f);
+    }
+}
+
+#[test]
+fn negative_zero_keeps_its_sign() {
+    assert_eq!(pack754_64(-0.0), 0x8000_0000_0000_0000);
+    assert!(unpack754_64(0x8000_0000_0000_0000).is_sign_negative());
+    assert_eq!(pack754_32(-0.0), 0x8000_0000);
+    assert!(unpack754_32(0x8000_0000).is_sign_negative());
+}
+
+#[test]
+fn nan_stays_nan() {
+    assert_eq!(pack754_64(f64::NAN), 0x7ff8_0000_0000_0000);
+    assert_eq!(pack754_32(f32::NAN), 0x7fc0_0000);
+    // the payload is lost, but it is still a NaN
+    let signaling = f64::from_bits(0x7ff0_0000_0000_0001);
+    assert!(pack754_checked(signaling).is_ok());
+    assert!(unpack754_64(0xfff0_0000_0000_0001).is_nan());
+    assert!(unpack754(0x7e01, 16, 5).is_nan());
+}
+
+#[test]
+fn infinities() {
+    assert_eq!(pack754_32(f32::INFINITY), 0x7f80_0000);
+    assert_eq!(pack754_32(f32::NEG_INFINITY), 0xff80_0000);
+    assert_eq!(unpack754_32(0xff80_0000), f32::NEG_INFINITY);
+    // too large for a float
+    assert_eq!(pack754(1e39, 32, 8), 0x7f80_0000);
+    // rounds up into infinity
+    assert_eq!(pack754(65520.0, 16, 5), 0x7c00);
+    assert_eq!(pack754(65504.0, 16, 5), 0x7bff);
+}
+
+#[test]
+fn subnormals() {
+    // smallest and largest subnormal float
+    assert_eq!(pack754(1.401298464324817e-45, 32, 8), 1);
+    assert_eq!(unpack754(1, 32, 8), 1.401298464324817e-45);
+    assert_eq!(pack754_32(f32::from_bits(0x007f_ffff)), 0x007f_ffff);
+    // half of the smallest subnormal is a tie, rounded to the even zero
+    assert_eq!(pack754(0.7e-45, 32, 8), 0);
+    // half-precision, where the book's example shows its limits
+    assert_eq!(pack754(5.960464477539063e-8, 16, 5), 1);
+    assert_eq!(unpack754(0x03ff, 16, 5), 6.097555160522461e-5);
+}
+
+#[test]
+fn known_encodings() {
+    assert_eq!(pack754_32(1.5), 0x3fc0_0000);
+    assert_eq!(pack754_32(-2.0), 0xc000_0000);
+    // 0.1 isn't exact, the last bit is rounded up
+    assert_eq!(pack754_64(0.1), 0x3fb9_9999_9999_999a);
+    assert_eq!(pack754(0.1, 32, 8), 0x3dcc_cccd);
+    assert_eq!(unpack754_64(0x3fb9_9999_9999_999a), 0.1);
+}
+
+#[test]
+fn htonll_is_big_endian() {
+    let x = 0x0102_0304_0506_0708u64;
+    assert_eq!(htonll(x).to_ne_bytes(), [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(ntohll(htonll(x)), x);
+}
+
+proptest! {
+    #[test]
+    fn every_double_matches_native(bits in any::<u64>()) {
+        let f = f64::from_bits(bits);
+        prop_assert!(pack754_checked(f).is_ok(), "{:?}", f);
+        let after = unpack754_64(pack754_64(f));
+        prop_assert!(after.to_bits() == bits || (f.is_nan() && after.is_nan()));
+    }
+
+    #[test]
+    fn every_float_matches_native(bits in any::<u32>()) {
+        let f = f32::from_bits(bits);
+        if !f.is_nan() {
+            prop_assert_eq!(pack754_32(f), bits);
+            prop_assert_eq!(unpack754_32(bits).to_bits(), bits);
+        }
+    }
+
+    #[test]
+    fn doubles_round_like_the_hardware(f in any::<f64>()) {
+        // `as f32` rounds to nearest, ties to even
+        let narrowed = f as f32;
+        if !narrowed.is_nan() {
+            prop_assert_eq!(pack754(f, 32, 8), u64::from(narrowed.to_bits()));
+        }
+    }
+}
+
+#[test]
+fn check_mode_from_the_command_line() {
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["ieee754", "--check", "-0", "nan", "-inf", "5e-324", "3.14"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.matches("to_be_bytes  : same").count(),
+        5,
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("on the wire  : 80 00 00 00 00 00 00 00"));
+}
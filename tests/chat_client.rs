@@ -0,0 +1,81 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+mod common;
+use common::Server;
+
+/// Run `beej-rs` with piped stdin and stdout, the lines of stdout arrive on the channel
+fn spawn(args: &[&str]) -> (Child, ChildStdin, Receiver<String>) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdin = child.stdin.take().unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in stdout.lines() {
+            if tx.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    (child, stdin, rx)
+}
+
+fn stderr(child: &mut Child) -> String {
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    stderr
+}
+
+#[test]
+fn chats_through_the_server() {
+    let server = Server::start(&["chat-server", "--port", "9138"]);
+
+    let (mut alice, mut alice_in, _alice_out) = spawn(&["chat-client", "--port", "9138"]);
+    let (mut bob, _bob_in, bob_out) = spawn(&["chat-client", "--port", "9138"]);
+
+    // bob might not be connected yet, so alice repeats herself until he hears her
+    let mut heard = false;
+    for _ in 0..50 {
+        alice_in.write_all(b"hello bob\n").unwrap();
+        if let Ok(line) = bob_out.recv_timeout(Duration::from_millis(100)) {
+            assert_eq!(line, "hello bob");
+            heard = true;
+            break;
+        }
+    }
+    assert!(heard, "bob never heard alice");
+
+    // EOF on stdin, alice leaves cleanly
+    drop(alice_in);
+    assert!(alice.wait().unwrap().success());
+
+    // the server leaves, bob gets the goodbye and stops too
+    let (status, _) = server.stop();
+    assert!(status.success(), "{}", status);
+    let goodbye = bob_out
+        .iter()
+        .find(|line| line != "hello bob")
+        .expect("no goodbye");
+    assert_eq!(goodbye, "Server is shutting down, bye!");
+    assert!(bob.wait().unwrap().success());
+    assert!(
+        stderr(&mut bob).contains("server closed the connection"),
+        "bob didn't notice the server leaving"
+    );
+}
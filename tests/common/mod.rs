@@ -0,0 +1,108 @@
+//! What the integration tests share: `beej-rs` running as a server
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+
+/// A server that is ready, its output is collected in the background.
+/// It's killed even when the test fails
+pub struct Server {
+    child: Child,
+    lines: Receiver<String>,
+    output: Option<JoinHandle<String>>,
+}
+
+impl Server {
+    /// Run `beej-rs` with `args`, and wait until it's listening
+    pub fn start(args: &[&str]) -> Server {
+        Server::start_when(args, "Listening on")
+    }
+
+    /// Run `beej-rs` with `args`, and wait for a line starting with `ready`
+    pub fn start_when(args: &[&str], ready: &str) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let mut line = String::new();
+        while !line.starts_with(ready) {
+            line.clear();
+            assert_ne!(stdout.read_line(&mut line).unwrap(), 0, "server died");
+        }
+        // keep reading, a full pipe would block the server
+        let (tx, lines) = mpsc::channel();
+        let output = thread::spawn(move || {
+            let mut output = line;
+            for line in stdout.lines() {
+                let line = line.unwrap();
+                output.push_str(&line);
+                output.push('\n');
+                // nobody listens once the test has what it waited for
+                let _ = tx.send(line);
+            }
+            output
+        });
+        Server {
+            child,
+            lines,
+            output: Some(output),
+        }
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// The next line the server prints, without the newline
+    pub fn next_line(&self) -> String {
+        self.lines
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap_or_else(|e| panic!("no line from the server: {}", e))
+    }
+
+    /// Wait for the server to print a line starting with `prefix`
+    pub fn wait_for(&self, prefix: &str) -> String {
+        loop {
+            let line = self
+                .lines
+                .recv_timeout(Duration::from_secs(5))
+                .unwrap_or_else(|e| panic!("no line starting with {:?}: {}", prefix, e));
+            if line.starts_with(prefix) {
+                return line;
+            }
+        }
+    }
+
+    /// Stop with `SIGTERM`, returning how it exited and everything it printed
+    pub fn stop(self) -> (ExitStatus, String) {
+        self.stop_with(Signal::SIGTERM)
+    }
+
+    pub fn stop_with(mut self, signal: Signal) -> (ExitStatus, String) {
+        signal::kill(Pid::from_raw(self.child.id() as i32), signal).unwrap();
+        let status = self.child.wait().unwrap();
+        let output = self.output.take().unwrap().join().unwrap();
+        (status, output)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        // nothing to do once it was stopped
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    io::Read,
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+    time::{Duration, Instant},
+};
+
+use beej_rs::connect::connect_timeout;
+use socket2::{Domain, Socket, Type};
+
+/// A listener whose accept queue is full, so `connect` to it hangs
+fn blackhole() -> (Socket, Vec<Socket>, SocketAddr) {
+    let listener = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    listener
+        .bind(&SocketAddr::from((Ipv4Addr::LOCALHOST, 0)).into())
+        .unwrap();
+    listener.listen(0).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mut fillers = Vec::new();
+    for _ in 0..2 {
+        let filler = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+        filler.set_nonblocking(true).unwrap();
+        let _ = filler.connect(&addr);
+        fillers.push(filler);
+    }
+    std::thread::sleep(Duration::from_millis(100));
+    (listener, fillers, addr.as_socket().unwrap())
+}
+
+/// Nobody listens there
+fn closed() -> SocketAddr {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[test]
+fn reports_every_failed_address() {
+    let (_listener, _fillers, hole) = blackhole();
+    let refused = closed();
+
+    let start = Instant::now();
+    let err = connect_timeout([hole, refused], Duration::from_millis(200)).unwrap_err();
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+
+    assert_eq!(err.attempts.len(), 2);
+    assert_eq!(err.attempts[0].0, hole);
+    assert_eq!(err.attempts[0].1.raw_os_error(), Some(libc::ETIMEDOUT));
+    assert_eq!(err.attempts[1].0, refused);
+    assert_eq!(err.attempts[1].1.raw_os_error(), Some(libc::ECONNREFUSED));
+}
+
+#[test]
+fn connects_after_a_timeout() {
+    let (_listener, _fillers, hole) = blackhole();
+    let server = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let (socket, connected) = connect_timeout([hole, addr], Duration::from_millis(100)).unwrap();
+    assert_eq!(connected, addr);
+
+    // back in blocking mode: recv waits for the data instead of failing with EAGAIN
+    let (mut accepted, _) = server.accept().unwrap();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        std::io::Write::write_all(&mut accepted, b"hi").unwrap();
+    });
+    let mut buf = [0u8; 2];
+    let mut stream = std::net::TcpStream::from(std::os::fd::OwnedFd::from(socket));
+    stream.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hi");
+    writer.join().unwrap();
+}
+
+#[test]
+fn no_addresses() {
+    let err = connect_timeout([], Duration::from_millis(100)).unwrap_err();
+    assert!(err.attempts.is_empty());
+    assert_eq!(err.to_string(), "no addresses to connect to");
+}
@@ -0,0 +1,207 @@
+use std::{
+    net::{IpAddr, UdpSocket},
+    process::{Command, Output},
+    time::Duration,
+};
+
+use beej_rs::{
+    discovery::{announcements, is_query, query, Announcement, Responder, MDNS_IPV4, SERVICE},
+    dns::{Message, Record, RecordData, RecordType, CLASS_IN},
+    interfaces::ipv4_interfaces,
+    types::Family,
+};
+
+mod common;
+use common::Server;
+
+fn lab1() -> Announcement {
+    Announcement {
+        name: "lab1".to_string(),
+        port: 9034,
+        family: Family::Ipv6,
+        target: "host.local".to_string(),
+    }
+}
+
+/// Through the wire format and back
+fn round_trip(message: &Message) -> Message {
+    Message::parse(&message.to_bytes().unwrap()).unwrap()
+}
+
+#[test]
+fn query_and_answer() {
+    let query = round_trip(&query(42));
+    assert!(is_query(&query));
+    assert_eq!(query.questions[0].name, SERVICE);
+    assert_eq!(query.questions[0].qtype, RecordType::Ptr);
+    assert!(!query.header.rd);
+
+    // legacy unicast: id and question are echoed, plain IN class
+    let answer = round_trip(&lab1().response(&query, true));
+    assert!(!is_query(&answer));
+    assert_eq!(answer.header.id, 42);
+    assert!(answer.header.qr && answer.header.aa);
+    assert_eq!(answer.questions, query.questions);
+    assert!(answer.additionals.iter().all(|r| r.class == CLASS_IN));
+    assert_eq!(announcements(&answer), [lab1()]);
+
+    // multicast: id 0, no question, SRV and TXT flush the caches
+    let answer = round_trip(&lab1().response(&query, false));
+    assert_eq!(answer.header.id, 0);
+    assert!(answer.questions.is_empty());
+    assert_eq!(answer.answers[0].class, CLASS_IN);
+    assert!(answer
+        .additionals
+        .iter()
+        .all(|r| r.class == CLASS_IN | 0x8000));
+    assert_eq!(announcements(&answer), [lab1()]);
+}
+
+#[test]
+fn other_questions_are_ignored() {
+    let mut other = query(1);
+    other.questions[0].name = "_http._tcp.local".to_string();
+    assert!(!is_query(&other));
+
+    let mut srv = query(1);
+    srv.questions[0].qtype = RecordType::Srv;
+    assert!(!is_query(&srv));
+
+    // names are case-insensitive, ANY asks for everything
+    let mut any = query(1);
+    any.questions[0].name = "_BEEJ-chat._tcp.LOCAL.".to_string();
+    any.questions[0].qtype = RecordType::Other(255);
+    assert!(is_query(&any));
+
+    // questions are not answers
+    assert!(announcements(&query(1)).is_empty());
+}
+
+#[test]
+fn announcements_from_other_responders() {
+    let mut answer = lab1().response(&query(0), false);
+    // another responder may put everything in the answers, without TXT
+    let srv = answer.additionals.remove(0);
+    answer.additionals.clear();
+    answer.answers.push(srv);
+    assert_eq!(
+        announcements(&answer),
+        [Announcement {
+            family: Family::Unspecified,
+            ..lab1()
+        }]
+    );
+
+    // an instance without SRV can't be reached
+    answer.answers.pop();
+    assert!(announcements(&answer).is_empty());
+
+    // a PTR for another service
+    let mut answer = lab1().response(&query(0), false);
+    answer.answers[0] = Record {
+        name: "_http._tcp.local".to_string(),
+        rtype: RecordType::Ptr,
+        class: CLASS_IN,
+        ttl: 120,
+        data: RecordData::Ptr("lab1._beej-chat._tcp.local".to_string()),
+    };
+    assert!(announcements(&answer).is_empty());
+}
+
+#[test]
+fn crafted_questions_dont_stop_the_responder() {
+    let responder = Responder::bind(lab1(), IpAddr::V4(MDNS_IPV4), 9160, Some("lo")).unwrap();
+    let running = responder.spawn();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect("127.0.0.1:9160").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let answer = || {
+        let mut buf = [0u8; 9000];
+        let len = socket.recv(&mut buf).unwrap();
+        Message::parse(&buf[..len]).unwrap()
+    };
+    // our question, then another one
+    let with_second_question = |id, label: u8, count| {
+        let mut bytes = query(id).to_bytes().unwrap();
+        bytes[5] = 2;
+        for _ in 0..count {
+            bytes.push(63);
+            bytes.extend_from_slice(&[label; 63]);
+        }
+        bytes
+    };
+
+    // 256 bytes of labels, then a pointer to the service name: dropped by the parser
+    let mut bytes = with_second_question(1, b'a', 4);
+    bytes.extend_from_slice(&[0xc0, 12, 0, 12, 0, 1]);
+    socket.send(&bytes).unwrap();
+
+    // short enough on the wire, but too long to write once decoded as UTF-8
+    let mut bytes = with_second_question(2, 0xff, 3);
+    bytes.extend_from_slice(&[0, 0, 12, 0, 1]);
+    socket.send(&bytes).unwrap();
+    let answer2 = answer();
+    assert_eq!(answer2.header.id, 2);
+    // only the question we answer is echoed
+    assert_eq!(answer2.questions, query(2).questions);
+
+    socket.send(&query(3).to_bytes().unwrap()).unwrap();
+    assert_eq!(answer().header.id, 3);
+    assert!(!running.is_finished());
+}
+
+fn discover(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["discover", "--timeout", "300"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn discover_the_chat_servers() {
+    let announce = ["--discovery-port", "9148", "--discovery-interface", "lo"];
+    let _lab1 = Server::start(
+        &[
+            &["chat-server", "-p", "9147", "--announce", "lab1"][..],
+            &announce,
+        ]
+        .concat(),
+    );
+    let _lab2 = Server::start(
+        &[
+            &["chat-server", "-p", "9149", "--announce", "lab2"][..],
+            &announce,
+        ]
+        .concat(),
+    );
+
+    let output = discover(&["--port", "9148", "--interface", "lo"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "lab1\t127.0.0.1:9147\tunspecified\nlab2\t127.0.0.1:9149\tunspecified\n"
+    );
+
+    // broadcasts reach the responders too, when there is somewhere to broadcast
+    if ipv4_interfaces().unwrap().iter().any(|i| i.can_broadcast) {
+        let output = discover(&["--port", "9148", "--broadcast"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("lab1\t"), "{}", stdout);
+        assert!(stdout.contains("\nlab2\t"), "{}", stdout);
+    }
+}
+
+#[test]
+fn nobody_answers() {
+    let output = discover(&["--port", "9150", "--interface", "lo"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .ends_with("discover: no server answered within 300ms\n"));
+
+    let output = discover(&["--family", "ipv6", "--broadcast"]);
+    assert_eq!(output.status.code(), Some(2));
+}
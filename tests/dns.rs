@@ -0,0 +1,419 @@
+use std::{
+    io::{Read, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, TcpListener, UdpSocket},
+    thread,
+    time::Duration,
+};
+
+use beej_rs::{
+    dns::{
+        parse_nsswitch_hosts, DnsError, Hosts, Message, Rcode, Record, RecordData, RecordType,
+        ResolvConf, Resolver, ResolverChain, Source, CLASS_IN,
+    },
+    types::Family,
+};
+
+fn record(name: &str, data: RecordData) -> Record {
+    let rtype = match data {
+        RecordData::A(_) => RecordType::A,
+        RecordData::Aaaa(_) => RecordType::Aaaa,
+        RecordData::Cname(_) => RecordType::Cname,
+        RecordData::Ptr(_) => RecordType::Ptr,
+        RecordData::Srv { .. } => RecordType::Srv,
+        RecordData::Txt(_) => RecordType::Txt,
+        RecordData::Other(_) => RecordType::Other(99),
+    };
+    Record {
+        name: name.to_string(),
+        rtype,
+        class: CLASS_IN,
+        ttl: 60,
+        data,
+    }
+}
+
+/// Answers for the fake zone, `over_tcp` tells which transport asked
+fn answer(query: &Message, over_tcp: bool) -> Message {
+    let mut response = query.clone();
+    response.header.qr = true;
+    response.header.ra = true;
+
+    let question = &query.questions[0];
+    let a = |ip: [u8; 4]| RecordData::A(Ipv4Addr::from(ip));
+    match (question.name.as_str(), question.qtype) {
+        ("host.test", RecordType::A) => {
+            response.answers = vec![record("host.test", a([192, 0, 2, 1]))];
+        }
+        ("host.test", RecordType::Aaaa) => {
+            let ip = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+            response.answers = vec![record("host.test", RecordData::Aaaa(ip))];
+        }
+        ("alias.test", RecordType::A) => {
+            response.answers = vec![
+                record("alias.test", RecordData::Cname("host.test".into())),
+                record("host.test", a([192, 0, 2, 1])),
+            ];
+        }
+        // a server that chokes on AAAA queries
+        ("v4only.test", RecordType::A) => {
+            response.answers = vec![record("v4only.test", a([192, 0, 2, 7]))];
+        }
+        ("v4only.test", RecordType::Aaaa) => response.header.rcode = Rcode::ServFail,
+        ("big.test", RecordType::A) if !over_tcp => response.header.tc = true,
+        ("big.test", RecordType::A) => {
+            response.answers = (1..=3)
+                .map(|i| record("big.test", a([198, 51, 100, i])))
+                .collect();
+        }
+        ("host.test", _) | ("alias.test", _) => {}
+        _ => response.header.rcode = Rcode::NxDomain,
+    }
+    response
+}
+
+/// Fake nameserver listening on the same loopback port over UDP and TCP
+fn fake_server() -> SocketAddr {
+    let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let addr = udp.local_addr().unwrap();
+    let tcp = TcpListener::bind(addr).unwrap();
+
+    thread::spawn(move || loop {
+        let mut buf = [0u8; 512];
+        let (len, peer) = udp.recv_from(&mut buf).unwrap();
+        let query = Message::parse(&buf[..len]).unwrap();
+        let response = answer(&query, false).to_bytes().unwrap();
+        udp.send_to(&response, peer).unwrap();
+    });
+    thread::spawn(move || {
+        for stream in tcp.incoming() {
+            let mut stream = stream.unwrap();
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).unwrap();
+            let mut buf = vec![0u8; u16::from_be_bytes(len) as usize];
+            stream.read_exact(&mut buf).unwrap();
+            let query = Message::parse(&buf).unwrap();
+            let response = answer(&query, true).to_bytes().unwrap();
+            stream
+                .write_all(&(response.len() as u16).to_be_bytes())
+                .unwrap();
+            stream.write_all(&response).unwrap();
+        }
+    });
+    addr
+}
+
+fn resolver() -> Resolver {
+    Resolver::new(ResolvConf {
+        nameservers: vec![fake_server()],
+        timeout: Duration::from_secs(2),
+        attempts: 1,
+        ..ResolvConf::default()
+    })
+}
+
+#[test]
+fn lookup_both_families() {
+    let lookup = resolver()
+        .lookup_ip("host.test", Family::Unspecified)
+        .unwrap();
+    assert_eq!(
+        lookup.addrs,
+        [
+            "[2001:db8::1]:0".parse::<SocketAddr>().unwrap(),
+            "192.0.2.1:0".parse().unwrap()
+        ]
+    );
+    assert_eq!(lookup.canonname, "host.test");
+}
+
+#[test]
+fn follows_cname() {
+    let lookup = resolver().lookup_ip("alias.test", Family::Ipv4).unwrap();
+    assert_eq!(
+        lookup.with_port(80),
+        [SocketAddr::from(([192, 0, 2, 1], 80))]
+    );
+    assert_eq!(lookup.canonname, "host.test");
+}
+
+#[test]
+fn truncated_answer_falls_back_to_tcp() {
+    let lookup = resolver().lookup_ip("big.test", Family::Ipv4).unwrap();
+    assert_eq!(lookup.addrs.len(), 3);
+}
+
+#[test]
+fn unknown_name_is_nxdomain() {
+    let err = resolver().lookup_ip("nope.test", Family::Ipv4).unwrap_err();
+    assert!(matches!(err, DnsError::NxDomain(_)), "{err:?}");
+}
+
+#[test]
+fn failed_aaaa_query_still_asks_for_a() {
+    let lookup = resolver()
+        .lookup_ip("v4only.test", Family::Unspecified)
+        .unwrap();
+    assert_eq!(lookup.addrs, [SocketAddr::from(([192, 0, 2, 7], 0))]);
+
+    // with nothing else to ask, the error is the answer
+    let err = resolver()
+        .lookup_ip("v4only.test", Family::Ipv6)
+        .unwrap_err();
+    assert!(matches!(err, DnsError::Server(Rcode::ServFail)), "{err:?}");
+}
+
+#[test]
+fn message_round_trip() {
+    let mut message = Message::query(0xbeef, "example.test", RecordType::Srv);
+    message.header.qr = true;
+    message.answers = vec![
+        record(
+            "_chat._tcp.example.test",
+            RecordData::Srv {
+                priority: 10,
+                weight: 5,
+                port: 9034,
+                target: "host.example.test".into(),
+            },
+        ),
+        record(
+            "1.2.0.192.in-addr.arpa",
+            RecordData::Ptr("host.example.test".into()),
+        ),
+        record(
+            "example.test",
+            RecordData::Txt(vec![b"hello".to_vec(), b"world".to_vec()]),
+        ),
+    ];
+
+    let parsed = Message::parse(&message.to_bytes().unwrap()).unwrap();
+    assert_eq!(parsed, message);
+}
+
+#[test]
+fn parse_compressed_names() {
+    #[rustfmt::skip]
+    let bytes = [
+        0x12, 0x34, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0,
+        // question: www.example.test A IN
+        3, b'w', b'w', b'w', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 4, b't', b'e', b's', b't', 0,
+        0, 1, 0, 1,
+        // answer: pointer to the question name (offset 12)
+        0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 7,
+        // CNAME: "mail" + pointer to "example.test" (offset 16)
+        4, b'm', b'a', b'i', b'l', 0xc0, 16,
+    ];
+    let message = Message::parse(&bytes).unwrap();
+    assert_eq!(message.answers[0].name, "www.example.test");
+    assert_eq!(
+        message.answers[0].data,
+        RecordData::Cname("mail.example.test".into())
+    );
+}
+
+#[test]
+fn pointer_loop_is_an_error() {
+    let bytes = [0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 12, 0, 1, 0, 1];
+    assert!(matches!(
+        Message::parse(&bytes),
+        Err(DnsError::Malformed(_))
+    ));
+}
+
+#[test]
+fn names_past_255_bytes_are_an_error() {
+    let label = |buf: &mut Vec<u8>| {
+        buf.push(63);
+        buf.extend_from_slice(&[b'a'; 63]);
+    };
+    // two questions
+    let mut bytes = vec![0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0];
+    // 193 bytes on the wire, a fine name
+    for _ in 0..3 {
+        label(&mut bytes);
+    }
+    bytes.extend_from_slice(&[0, 0, 12, 0, 1]);
+    // one more label before a pointer to the first one makes 257
+    label(&mut bytes);
+    bytes.extend_from_slice(&[0xc0, 12, 0, 12, 0, 1]);
+    assert!(matches!(
+        Message::parse(&bytes),
+        Err(DnsError::Malformed("name longer than 255 bytes"))
+    ));
+
+    // the first one alone is fine
+    bytes[5] = 1;
+    let message = Message::parse(&bytes).unwrap();
+    assert_eq!(message.questions[0].name.len(), 3 * 63 + 2);
+}
+
+#[test]
+fn parse_resolv_conf() {
+    let conf = ResolvConf::parse(
+        "# comment\n\
+         nameserver 192.0.2.53\n\
+         nameserver fe80::1%lo\n\
+         nameserver fe80::2%3\n\
+         nameserver fe80::3%no-such-interface\n\
+         search example.test corp.test\n\
+         options ndots:2 timeout:1 attempts:3 rotate\n",
+    );
+    // the zone is the scope id, like in the hosts file
+    let lo = nix::net::if_::if_nametoindex("lo").unwrap();
+    assert_eq!(
+        conf.nameservers,
+        [
+            SocketAddr::from(([192, 0, 2, 53], 53)),
+            SocketAddrV6::new("fe80::1".parse().unwrap(), 53, 0, lo).into(),
+            SocketAddrV6::new("fe80::2".parse().unwrap(), 53, 0, 3).into(),
+        ]
+    );
+    assert_eq!(conf.search, ["example.test", "corp.test"]);
+    assert_eq!(conf.ndots, 2);
+    assert_eq!(conf.timeout, Duration::from_secs(1));
+    assert_eq!(conf.attempts, 3);
+}
+
+#[test]
+fn resolv_conf_timeout_is_clamped() {
+    // a zero timeout would make every socket call fail
+    let conf = ResolvConf::parse("options timeout:0\n");
+    assert_eq!(conf.timeout, Duration::from_secs(1));
+    let conf = ResolvConf::parse("options timeout:3600\n");
+    assert_eq!(conf.timeout, Duration::from_secs(30));
+    let conf = ResolvConf::parse("options timeout:-1\n");
+    assert_eq!(conf.timeout, ResolvConf::default().timeout);
+}
+
+#[test]
+fn missing_resolv_conf_falls_back_to_localhost() {
+    let localhost = [SocketAddr::from((Ipv4Addr::LOCALHOST, 53))];
+    let resolver = Resolver::from_file("/nonexistent/resolv.conf").unwrap();
+    assert_eq!(resolver.conf().nameservers, localhost);
+
+    let dir = std::env::temp_dir().join(format!("beej-resolv-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("resolv.conf");
+    std::fs::write(&path, "search example.test\n").unwrap();
+    let resolver = Resolver::from_file(&path).unwrap();
+    assert_eq!(resolver.conf().nameservers, localhost);
+    assert_eq!(resolver.conf().search, ["example.test"]);
+
+    // other errors are still errors
+    assert!(matches!(Resolver::from_file(&dir), Err(DnsError::Io(_))));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+const HOSTS: &str = "\
+127.0.0.1   localhost
+# the same name on several lines gives several addresses
+10.0.0.1    multi.test multi
+10.0.0.2    multi.test
+::1         localhost ip6-localhost
+fe80::1%lo  link.test
+fe80::2%3   numeric-zone.test
+bogus       ignored.test
+10.0.0.3
+";
+
+#[test]
+fn hosts_multiple_addresses() {
+    let hosts = Hosts::parse(HOSTS);
+    let lookup = hosts.lookup_ip("Multi.Test", Family::Unspecified).unwrap();
+    assert_eq!(lookup.canonname, "multi.test");
+    assert_eq!(
+        lookup.with_port(1),
+        [
+            SocketAddr::from(([10, 0, 0, 1], 1)),
+            SocketAddr::from(([10, 0, 0, 2], 1))
+        ]
+    );
+
+    // IPv6 goes first, and the family filters
+    let lookup = hosts.lookup_ip("localhost", Family::Unspecified).unwrap();
+    assert_eq!(lookup.addrs[0].ip(), Ipv6Addr::LOCALHOST);
+    assert_eq!(lookup.addrs[1].ip(), Ipv4Addr::LOCALHOST);
+    let lookup = hosts.lookup_ip("localhost", Family::Ipv4).unwrap();
+    assert_eq!(lookup.addrs.len(), 1);
+
+    assert!(hosts.lookup_ip("multi", Family::Ipv6).is_none());
+    assert!(hosts
+        .lookup_ip("ignored.test", Family::Unspecified)
+        .is_none());
+    assert_eq!(hosts.entries.len(), 6);
+}
+
+#[test]
+fn hosts_zone_ids() {
+    let hosts = Hosts::parse(HOSTS);
+    let lo = nix::net::if_::if_nametoindex("lo").unwrap();
+
+    let SocketAddr::V6(link) = hosts.lookup_ip("link.test", Family::Ipv6).unwrap().addrs[0] else {
+        panic!("expected IPv6");
+    };
+    assert_eq!(link.scope_id(), lo);
+
+    let SocketAddr::V6(numeric) = hosts
+        .lookup_ip("numeric-zone.test", Family::Ipv6)
+        .unwrap()
+        .addrs[0]
+    else {
+        panic!("expected IPv6");
+    };
+    assert_eq!(numeric.scope_id(), 3);
+}
+
+#[test]
+fn chain_prefers_hosts_then_dns() {
+    let hosts = Hosts::parse("10.9.9.9 host.test\n");
+    let chain = ResolverChain::new(vec![Source::Hosts(hosts), Source::resolver(resolver())]);
+
+    let lookup = chain.lookup_ip("host.test", Family::Ipv4).unwrap();
+    assert_eq!(lookup.addrs, [SocketAddr::from(([10, 9, 9, 9], 0))]);
+
+    let lookup = chain.lookup_ip("alias.test", Family::Ipv4).unwrap();
+    assert_eq!(lookup.addrs, [SocketAddr::from(([192, 0, 2, 1], 0))]);
+
+    let err = chain.lookup_ip("nope.test", Family::Ipv4).unwrap_err();
+    assert!(matches!(err, DnsError::NxDomain(_)), "{err:?}");
+}
+
+#[test]
+fn chain_reads_dns_config_only_when_needed() {
+    let dir = std::env::temp_dir().join(format!("beej-chain-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let hosts_file = dir.join("hosts");
+    std::fs::write(&hosts_file, "10.1.2.3 only.test\n").unwrap();
+    // can't be read as a file, so dns can't be configured
+    let resolv_conf = &dir;
+
+    // a missing nsswitch.conf means files then dns
+    let chain = ResolverChain::from_files(dir.join("nsswitch.conf"), &hosts_file, resolv_conf);
+    let lookup = chain.lookup_ip("only.test", Family::Ipv4).unwrap();
+    assert_eq!(lookup.addrs, [SocketAddr::from(([10, 1, 2, 3], 0))]);
+    let err = chain.lookup_ip("nope.test", Family::Ipv4).unwrap_err();
+    assert!(matches!(err, DnsError::Io(_)), "{err:?}");
+
+    // without dns, only the hosts file is asked
+    let nsswitch = dir.join("nsswitch.conf");
+    std::fs::write(&nsswitch, "hosts: files\n").unwrap();
+    let chain = ResolverChain::from_files(&nsswitch, &hosts_file, resolv_conf);
+    let err = chain.lookup_ip("nope.test", Family::Ipv4).unwrap_err();
+    assert!(matches!(err, DnsError::NxDomain(_)), "{err:?}");
+
+    // a hosts file we are given is asked whatever the system nsswitch.conf says
+    let chain = ResolverChain::from_system(Some(&hosts_file));
+    let lookup = chain.lookup_ip("only.test", Family::Ipv4).unwrap();
+    assert_eq!(lookup.addrs, [SocketAddr::from(([10, 1, 2, 3], 0))]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn nsswitch_hosts_line() {
+    let services = parse_nsswitch_hosts(
+        "passwd: files\nhosts: files mdns4_minimal [NOTFOUND=return] dns # comment\n",
+    );
+    assert_eq!(services, ["files", "mdns4_minimal", "dns"]);
+    assert_eq!(parse_nsswitch_hosts(""), ["files", "dns"]);
+}
@@ -0,0 +1,223 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+use beej_rs::{
+    framing::{FrameCodec, FrameError, MAX_FRAME_SIZE},
+    types::{Backend, Framing},
+};
+
+mod common;
+use common::Server;
+
+fn decode_all(codec: &mut FrameCodec) -> Vec<Vec<u8>> {
+    let mut frames = Vec::new();
+    while let Some(frame) = codec.decode().unwrap() {
+        frames.push(frame);
+    }
+    frames
+}
+
+#[test]
+fn length_prefixes_round_trip_byte_by_byte() {
+    for framing in [Framing::U16, Framing::U32] {
+        let mut codec = FrameCodec::new(framing, MAX_FRAME_SIZE);
+        let messages: [&[u8]; 3] = [b"hello", b"", b"world!"];
+        let wire: Vec<u8> = messages
+            .iter()
+            .flat_map(|m| codec.encode(m).unwrap())
+            .collect();
+
+        let mut frames = Vec::new();
+        for byte in &wire {
+            codec.push(std::slice::from_ref(byte));
+            frames.extend(decode_all(&mut codec));
+        }
+        assert_eq!(frames, messages);
+        assert_eq!(codec.buffered(), 0);
+    }
+}
+
+#[test]
+fn prefix_is_network_byte_order() {
+    let codec = FrameCodec::new(Framing::U16, MAX_FRAME_SIZE);
+    assert_eq!(codec.encode(b"hi").unwrap(), b"\x00\x02hi");
+    let codec = FrameCodec::new(Framing::U32, 1 << 20);
+    let frame = vec![0u8; 0x010203];
+    assert_eq!(&codec.encode(&frame).unwrap()[..4], b"\x00\x01\x02\x03");
+}
+
+#[test]
+fn merged_frames_are_split() {
+    let mut codec = FrameCodec::new(Framing::Line, MAX_FRAME_SIZE);
+    codec.push(b"one\ntwo\r\nthr");
+    assert_eq!(decode_all(&mut codec), [b"one".to_vec(), b"two".to_vec()]);
+    assert_eq!(codec.buffered(), 3);
+    codec.push(b"ee\n");
+    assert_eq!(decode_all(&mut codec), [b"three".to_vec()]);
+}
+
+#[test]
+fn max_size_is_enforced() {
+    let mut codec = FrameCodec::new(Framing::U32, 4);
+    // the header is enough to know
+    codec.push(&[0, 0, 0, 5]);
+    assert_eq!(codec.decode(), Err(FrameError::TooLarge { len: 5, max: 4 }));
+    assert_eq!(
+        codec.encode(b"12345"),
+        Err(FrameError::TooLarge { len: 5, max: 4 })
+    );
+
+    // no newline in sight and already too long
+    let mut codec = FrameCodec::new(Framing::Line, 4);
+    codec.push(b"12345");
+    assert_eq!(codec.decode(), Err(FrameError::TooLarge { len: 5, max: 4 }));
+
+    // a u16 prefix can't describe more, whatever we ask for
+    let codec = FrameCodec::new(Framing::U16, 1 << 20);
+    assert_eq!(codec.max_size(), 65535);
+    assert!(codec.encode(&vec![0u8; 65536]).is_err());
+}
+
+#[test]
+fn line_frames_cannot_hold_a_newline() {
+    let codec = FrameCodec::new(Framing::Line, MAX_FRAME_SIZE);
+    assert_eq!(codec.encode(b"a\nb"), Err(FrameError::Newline));
+    assert_eq!(codec.encode(b"ab").unwrap(), b"ab\n");
+}
+
+#[test]
+fn raw_is_whatever_was_received() {
+    let mut codec = FrameCodec::new(Framing::Raw, MAX_FRAME_SIZE);
+    assert_eq!(codec.decode(), Ok(None));
+    codec.push(b"a\nb");
+    codec.push(b"c");
+    assert_eq!(decode_all(&mut codec), [b"a\nbc".to_vec()]);
+}
+
+#[test]
+fn raw_is_cut_at_the_max_size() {
+    let mut codec = FrameCodec::new(Framing::Raw, 4);
+    codec.push(b"0123456789");
+    let frames = decode_all(&mut codec);
+    assert_eq!(frames, [b"0123".to_vec(), b"4567".to_vec(), b"89".to_vec()]);
+    // so every piece can be sent again
+    for frame in frames {
+        assert_eq!(codec.encode(&frame).unwrap(), frame);
+    }
+
+    let mut codec = FrameCodec::new(Framing::Raw, 0);
+    codec.push(b"a");
+    assert_eq!(codec.decode(), Err(FrameError::TooLarge { len: 1, max: 0 }));
+}
+
+fn connect(port: u16) -> TcpStream {
+    let stream = TcpStream::connect(("localhost", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+}
+
+fn read_frame(stream: &mut TcpStream) -> Vec<u8> {
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).unwrap();
+    let mut frame = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut frame).unwrap();
+    frame
+}
+
+fn relays_whole_frames(backend: Backend, port: u16) {
+    let _server = Server::start(&[
+        "chat-server",
+        "--port",
+        &port.to_string(),
+        "--backend",
+        &backend.to_string(),
+        "--framing",
+        "u16",
+        "--max-frame-size",
+        "100",
+    ]);
+    let mut a = connect(port);
+    let mut b = connect(port);
+
+    // both are connected once a frame from one reaches the other
+    let mut connected = false;
+    for _ in 0..50 {
+        a.write_all(b"\x00\x04ping").unwrap();
+        b.set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let mut len = [0u8; 2];
+        if b.read_exact(&mut len).is_ok() {
+            let mut ping = [0u8; 4];
+            b.read_exact(&mut ping).unwrap();
+            connected = true;
+            break;
+        }
+    }
+    assert!(connected, "clients never saw each other");
+    b.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    a.write_all(b"\x00\x03end").unwrap();
+    while read_frame(&mut b) != b"end" {}
+
+    // one frame dribbling in, then two in a single write
+    for byte in b"\x00\x05hello" {
+        a.write_all(&[*byte]).unwrap();
+        thread::sleep(Duration::from_millis(5));
+    }
+    a.write_all(b"\x00\x03one\x00\x03two").unwrap();
+    assert_eq!(read_frame(&mut b), b"hello");
+    assert_eq!(read_frame(&mut b), b"one");
+    assert_eq!(read_frame(&mut b), b"two");
+
+    // too big: a is disconnected, and b never sees it
+    a.write_all(b"\x01\x00").unwrap();
+    let mut rest = Vec::new();
+    a.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+    b.set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    assert!(b.read(&mut [0u8; 1]).is_err());
+}
+
+#[test]
+fn poll_backend_relays_whole_frames() {
+    relays_whole_frames(Backend::Poll, 9139);
+}
+
+#[test]
+fn select_backend_relays_whole_frames() {
+    relays_whole_frames(Backend::Select, 9140);
+}
+
+#[test]
+fn raw_chunks_over_the_max_size_are_relayed() {
+    let server = Server::start(&[
+        "chat-server",
+        "--port",
+        "9157",
+        "--framing",
+        "raw",
+        "--max-frame-size",
+        "16",
+    ]);
+    let mut a = connect(9157);
+    let mut b = connect(9157);
+    server.wait_for("[Server] New connection");
+    server.wait_for("[Server] New connection");
+
+    let message = b"more than sixteen bytes in one send\n";
+    a.write_all(message).unwrap();
+    let mut received = vec![0u8; message.len()];
+    b.read_exact(&mut received).unwrap();
+    assert_eq!(received, message);
+
+    // nobody was disconnected
+    let (status, output) = server.stop();
+    assert!(status.success(), "{}", status);
+    assert!(output.contains("saying goodbye to 2 clients"), "{}", output);
+}
@@ -0,0 +1,71 @@
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener},
+    time::{Duration, Instant},
+};
+
+use beej_rs::{connect::happy_eyeballs, partial};
+use socket2::{Domain, Socket, Type};
+
+/// A listener that never completes a handshake: the accept queue is full,
+/// so the kernel drops every new SYN and `connect` hangs.
+fn blackhole(addr: SocketAddr) -> (Socket, Vec<Socket>) {
+    let listener = Socket::new(Domain::for_address(addr), Type::STREAM, None).unwrap();
+    listener.bind(&addr.into()).unwrap();
+    listener.listen(0).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // fill the accept queue, never accepting
+    let mut fillers = Vec::new();
+    for _ in 0..2 {
+        let filler = Socket::new(
+            Domain::for_address(addr.as_socket().unwrap()),
+            Type::STREAM,
+            None,
+        )
+        .unwrap();
+        filler.set_nonblocking(true).unwrap();
+        let _ = filler.connect(&addr);
+        fillers.push(filler);
+    }
+    std::thread::sleep(Duration::from_millis(100));
+    (listener, fillers)
+}
+
+#[test]
+fn skips_blackholed_family() {
+    let (v6, _fillers) = blackhole(SocketAddr::from((Ipv6Addr::LOCALHOST, 0)));
+    let v6_addr = v6.local_addr().unwrap().as_socket().unwrap();
+
+    let v4 = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let v4_addr = v4.local_addr().unwrap();
+
+    // two unreachable IPv6 addresses first: interleaving makes IPv4 the second attempt
+    let start = Instant::now();
+    let (socket, addr) =
+        happy_eyeballs([v6_addr, v6_addr, v4_addr], Duration::from_millis(50)).unwrap();
+
+    assert_eq!(addr, v4_addr);
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    let (mut accepted, _) = v4.accept().unwrap();
+    std::io::Write::write_all(&mut accepted, b"hi").unwrap();
+    let mut buf = [0u8; 2];
+    partial::recv_exact(&socket, &mut buf).unwrap();
+    assert_eq!(&buf, b"hi");
+}
+
+#[test]
+fn reports_every_address_when_nothing_connects() {
+    // bind then drop, so nobody listens on the port
+    let closed = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let err = happy_eyeballs([closed, closed], Duration::from_millis(50)).unwrap_err();
+    assert_eq!(err.attempts.len(), 2);
+    for (addr, e) in &err.attempts {
+        assert_eq!(*addr, closed);
+        assert_eq!(e.kind(), std::io::ErrorKind::ConnectionRefused);
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    net::UdpSocket,
+    process::{Command, Output},
+};
+
+use beej_rs::multicast::{self, SourceMembership};
+use nix::{net::if_::InterfaceFlags, sys::socket::setsockopt};
+
+mod common;
+use common::Server;
+
+fn receiver(args: &[&str]) -> Server {
+    Server::start_when(&[&["multicast-recv"][..], args].concat(), "Joined ")
+}
+
+fn send(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .arg("multicast-send")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stop(server: Server) {
+    let (status, _) = server.stop();
+    assert!(status.success(), "{}", status);
+}
+
+/// An interface that is up, does multicast and has an IPv6 address, the loopback doesn't
+fn ipv6_multicast_interface() -> Option<String> {
+    nix::ifaddrs::getifaddrs()
+        .unwrap()
+        .find(|ifaddr| {
+            ifaddr
+                .flags
+                .contains(InterfaceFlags::IFF_UP | InterfaceFlags::IFF_MULTICAST)
+                && !ifaddr.flags.contains(InterfaceFlags::IFF_LOOPBACK)
+                && ifaddr
+                    .address
+                    .as_ref()
+                    .is_some_and(|a| a.as_sockaddr_in6().is_some())
+        })
+        .map(|ifaddr| ifaddr.interface_name)
+}
+
+#[test]
+fn ipv4_on_loopback() {
+    let receiver = receiver(&["239.255.0.1", "--port", "9144", "--interface", "lo"]);
+    let output = send(&[
+        "239.255.0.1",
+        "hello",
+        "--port",
+        "9144",
+        "--interface",
+        "lo",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Sending message to 239.255.0.1:9144\n"
+    );
+    let line = receiver.next_line();
+    assert!(line.starts_with("got packet from 127.0.0.1:"), "{}", line);
+    assert!(line.ends_with(": \"hello\""), "{}", line);
+    stop(receiver);
+}
+
+#[test]
+fn source_specific_join() {
+    let wanted = receiver(&[
+        "232.1.1.1",
+        "--port",
+        "9145",
+        "--interface",
+        "lo",
+        "--source",
+        "127.0.0.1",
+    ]);
+    let other = receiver(&[
+        "232.1.1.1",
+        "--port",
+        "9145",
+        "--interface",
+        "lo",
+        "--source",
+        "127.0.0.2",
+    ]);
+    // from 127.0.0.2, for the second receiver only
+    let socket = UdpSocket::bind("127.0.0.2:0").unwrap();
+    multicast::set_send_options(&socket, "232.1.1.1".parse().unwrap(), 1, true, Some("lo"))
+        .unwrap();
+    socket.send_to(b"from .2", "232.1.1.1:9145").unwrap();
+    assert!(send(&[
+        "232.1.1.1",
+        "from .1",
+        "--port",
+        "9145",
+        "--interface",
+        "lo"
+    ])
+    .status
+    .success());
+
+    assert!(wanted.next_line().ends_with(": \"from .1\""));
+    assert!(other.next_line().ends_with(": \"from .2\""));
+    stop(wanted);
+    stop(other);
+}
+
+#[test]
+fn ipv6_with_and_without_loop() {
+    let Some(interface) = ipv6_multicast_interface() else {
+        eprintln!("no IPv6 multicast interface, skipping");
+        return;
+    };
+    let receiver = receiver(&["ff02::4242", "--port", "9146", "--interface", &interface]);
+    let args = ["ff02::4242", "--port", "9146", "--interface", &interface];
+    // not looped back, so the receiver only gets the second one
+    let output = send(&[&args[..], &["not for us", "--no-loop"]].concat());
+    assert!(output.status.success());
+    let output = send(&[&args[..], &["hello"]].concat());
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Sending message to [ff02::4242%"));
+
+    let line = receiver.next_line();
+    assert!(line.ends_with(": \"hello\""), "{}", line);
+    stop(receiver);
+}
+
+#[test]
+fn bad_groups() {
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["multicast-recv", "10.0.0.1"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "multicast-recv: can't join 10.0.0.1: 10.0.0.1 is not a multicast address\n"
+    );
+
+    let output = send(&["239.255.0.1", "hi", "--interface", "no-such-interface"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "multicast-send: can't send to 239.255.0.1: no IPv4 interface named no-such-interface\n"
+    );
+
+    // the families of the group and the source have to match
+    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    let membership = SourceMembership {
+        group: "232.1.1.1".parse().unwrap(),
+        source: "::1".parse().unwrap(),
+        interface: 0,
+    };
+    assert_eq!(
+        setsockopt(&socket, multicast::JoinSourceGroup, &membership),
+        Err(nix::errno::Errno::EINVAL)
+    );
+}
@@ -0,0 +1,134 @@
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    thread,
+    time::Duration,
+};
+
+use beej_rs::partial::{recv_exact, sendall};
+use socket2::{Domain, Socket, Type};
+
+/// A connected TCP pair over loopback, the sender with the smallest send buffer we can get
+fn pair() -> (Socket, Socket) {
+    let listener = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    listener
+        .bind(&SocketAddr::from((Ipv4Addr::LOCALHOST, 0)).into())
+        .unwrap();
+    listener.listen(1).unwrap();
+
+    let sender = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    // the kernel rounds this up to its minimum, still far below what we send
+    sender.set_send_buffer_size(1).unwrap();
+    sender.connect(&listener.local_addr().unwrap()).unwrap();
+    let (receiver, _) = listener.accept().unwrap();
+    (sender, receiver)
+}
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+/// Read everything in pieces that don't match the sends
+fn reader(receiver: Socket, len: usize) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut received = vec![0u8; len];
+        for chunk in received.chunks_mut(10_000) {
+            recv_exact(&receiver, chunk).unwrap();
+        }
+        received
+    })
+}
+
+#[test]
+fn sendall_finishes_short_writes_on_nonblocking_socket() {
+    let (sender, receiver) = pair();
+    sender.set_nonblocking(true).unwrap();
+    let data = payload(1 << 21);
+
+    // make sure a single send really is short
+    let first = sender.send(&data).unwrap();
+    assert!(first < data.len(), "send wasn't short: {}", first);
+
+    let reader = reader(receiver, data.len());
+    sendall(&sender, &data[first..]).unwrap();
+    assert_eq!(reader.join().unwrap(), data);
+}
+
+#[test]
+fn sendall_on_blocking_socket() {
+    let (sender, receiver) = pair();
+    let data = payload(1 << 21);
+
+    let reader = reader(receiver, data.len());
+    sendall(&sender, &data).unwrap();
+    assert_eq!(reader.join().unwrap(), data);
+}
+
+#[test]
+fn sendall_reports_bytes_sent_when_peer_leaves() {
+    let (sender, receiver) = pair();
+    let data = payload(1 << 21);
+
+    // read a little, then close with unread data, which resets the connection
+    let reader = thread::spawn(move || {
+        let mut buf = [0u8; 1000];
+        recv_exact(&receiver, &mut buf).unwrap();
+    });
+    let err = sendall(&sender, &data).unwrap_err();
+    reader.join().unwrap();
+
+    assert!(
+        matches!(
+            err.error.kind(),
+            io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset
+        ),
+        "{}",
+        err
+    );
+    assert!(err.len >= 1000 && err.len < data.len(), "{}", err);
+}
+
+#[test]
+fn recv_exact_reports_early_eof() {
+    let (sender, receiver) = pair();
+    sendall(&sender, b"abc").unwrap();
+    drop(sender);
+
+    let mut buf = [0u8; 10];
+    let err = recv_exact(&receiver, &mut buf).unwrap_err();
+    assert_eq!(err.error.kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(err.len, 3);
+    assert_eq!(&buf[..3], b"abc");
+    assert_eq!(err.to_string(), "unexpected end of file after 3 bytes");
+}
+
+#[test]
+fn recv_exact_waits_on_nonblocking_socket() {
+    let (sender, receiver) = pair();
+    receiver.set_nonblocking(true).unwrap();
+
+    let writer = thread::spawn(move || {
+        for piece in [&b"hel"[..], b"lo"] {
+            thread::sleep(Duration::from_millis(50));
+            sendall(&sender, piece).unwrap();
+        }
+    });
+    let mut buf = [0u8; 5];
+    recv_exact(&receiver, &mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+    writer.join().unwrap();
+}
+
+#[test]
+fn recv_exact_times_out_on_blocking_socket() {
+    let (sender, receiver) = pair();
+    receiver
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    sendall(&sender, b"ab").unwrap();
+
+    let mut buf = [0u8; 5];
+    let err = recv_exact(&receiver, &mut buf).unwrap_err();
+    assert_eq!(err.error.kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(err.len, 2);
+}
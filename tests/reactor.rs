@@ -0,0 +1,302 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream},
+    os::{
+        fd::{AsFd, AsRawFd, FromRawFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use beej_rs::{
+    reactor::{self, Event, EventLoop, Interest},
+    types::Backend,
+};
+use socket2::{Domain, Socket, Type};
+
+mod common;
+use common::Server;
+
+const BACKENDS: [Backend; 3] = [Backend::Poll, Backend::Select, Backend::Epoll];
+
+fn wait(event_loop: &mut dyn EventLoop, timeout: Duration) -> Vec<Event> {
+    let mut events = Vec::new();
+    event_loop.wait(&mut events, Some(timeout)).unwrap();
+    events
+}
+
+#[test]
+fn nothing_ready_times_out() {
+    for backend in BACKENDS {
+        let mut event_loop = reactor::new(backend).unwrap();
+        assert_eq!(event_loop.backend(), backend);
+        let (a, _b) = UnixStream::pair().unwrap();
+        event_loop.register(a.as_fd(), Interest::READABLE).unwrap();
+
+        let start = Instant::now();
+        let events = wait(event_loop.as_mut(), Duration::from_millis(50));
+        assert!(events.is_empty(), "{}: {:?}", backend, events);
+        assert!(start.elapsed() >= Duration::from_millis(40), "{}", backend);
+    }
+}
+
+#[test]
+fn readable_and_writable() {
+    for backend in BACKENDS {
+        let mut event_loop = reactor::new(backend).unwrap();
+        let (a, mut b) = UnixStream::pair().unwrap();
+        let fd = a.as_raw_fd();
+
+        // an empty send buffer has room
+        event_loop
+            .register(a.as_fd(), Interest::READABLE | Interest::WRITABLE)
+            .unwrap();
+        let events = wait(event_loop.as_mut(), Duration::from_secs(1));
+        let writable = Event {
+            fd,
+            readable: false,
+            writable: true,
+        };
+        assert_eq!(events, [writable], "{}", backend);
+
+        b.write_all(b"hello").unwrap();
+        event_loop
+            .reregister(a.as_fd(), Interest::READABLE)
+            .unwrap();
+        let events = wait(event_loop.as_mut(), Duration::from_secs(1));
+        let readable = Event {
+            fd,
+            readable: true,
+            writable: false,
+        };
+        assert_eq!(events, [readable], "{}", backend);
+
+        // level-triggered: still readable until we read it
+        let events = wait(event_loop.as_mut(), Duration::from_secs(1));
+        assert_eq!(events, [readable], "{}", backend);
+
+        event_loop.deregister(a.as_fd()).unwrap();
+        let events = wait(event_loop.as_mut(), Duration::from_millis(10));
+        assert!(events.is_empty(), "{}: {:?}", backend, events);
+    }
+}
+
+#[test]
+fn hangup_is_readable() {
+    for backend in BACKENDS {
+        let mut event_loop = reactor::new(backend).unwrap();
+        let (a, b) = UnixStream::pair().unwrap();
+        event_loop.register(a.as_fd(), Interest::READABLE).unwrap();
+        drop(b);
+
+        let events = wait(event_loop.as_mut(), Duration::from_secs(1));
+        assert_eq!(events.len(), 1, "{}: {:?}", backend, events);
+        assert_eq!(events[0].fd, a.as_raw_fd());
+        assert!(events[0].readable, "{}", backend);
+    }
+}
+
+#[test]
+fn registering_twice_and_unknown_fds() {
+    for backend in BACKENDS {
+        let mut event_loop = reactor::new(backend).unwrap();
+        let (a, b) = UnixStream::pair().unwrap();
+        event_loop.register(a.as_fd(), Interest::READABLE).unwrap();
+
+        let err = event_loop
+            .register(a.as_fd(), Interest::READABLE)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists, "{}", backend);
+        let err = event_loop
+            .reregister(b.as_fd(), Interest::READABLE)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound, "{}", backend);
+        let err = event_loop.deregister(b.as_fd()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound, "{}", backend);
+    }
+}
+
+#[test]
+fn select_refuses_fds_past_fd_setsize() {
+    let (a, _b) = UnixStream::pair().unwrap();
+    // SAFETY: a is open, the duplicate is ours to close
+    let high = unsafe { libc::fcntl(a.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 1024) };
+    if high < 0 {
+        eprintln!(
+            "skipping, can't open fd 1024: {}",
+            io::Error::last_os_error()
+        );
+        return;
+    }
+    let high = unsafe { OwnedFd::from_raw_fd(high) };
+
+    let mut event_loop = reactor::new(Backend::Select).unwrap();
+    let err = event_loop
+        .register(high.as_fd(), Interest::READABLE)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // the others have no such limit
+    for backend in [Backend::Poll, Backend::Epoll] {
+        let mut event_loop = reactor::new(backend).unwrap();
+        event_loop
+            .register(high.as_fd(), Interest::READABLE)
+            .unwrap();
+    }
+}
+
+fn connect(port: u16) -> TcpStream {
+    let stream = TcpStream::connect(("localhost", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+}
+
+/// Both clients are connected once a message from one reaches the other
+fn connect_pair(port: u16) -> (TcpStream, TcpStream) {
+    let mut a = connect(port);
+    let mut b = connect(port);
+    let mut buf = [0u8; 5];
+    for _ in 0..50 {
+        a.write_all(b"ping\n").unwrap();
+        b.set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        if b.read_exact(&mut buf).is_ok() {
+            b.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            return (a, b);
+        }
+    }
+    panic!("clients never saw each other");
+}
+
+/// A client that sends, then resets the connection instead of closing it
+fn reset(port: u16) {
+    let addr: SocketAddr = ([127, 0, 0, 1], port).into();
+    let socket = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    socket.connect(&addr.into()).unwrap();
+    socket.send(b"going away\n").unwrap();
+    // a zero linger sends RST when we close
+    socket.set_linger(Some(Duration::ZERO)).unwrap();
+}
+
+fn relays(backend: Backend, port: u16) {
+    let server = Server::start(&[
+        "chat-server",
+        "--port",
+        &port.to_string(),
+        "--backend",
+        &backend.to_string(),
+        "--goodbye",
+        "bye now\n",
+    ]);
+    let (mut a, mut b) = connect_pair(port);
+    reset(port);
+    // it's the only client that leaves
+    let closed = server.wait_for("[Client]");
+    assert!(
+        closed.contains("closing") || closed.ends_with("closed the connection"),
+        "{}",
+        closed
+    );
+
+    // the reset only cost the server that client
+    a.write_all(b"still here\n").unwrap();
+    let mut reader = BufReader::new(&mut b);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        if line == "still here\n" {
+            break;
+        }
+        // the pings that were still on the way, or what the reset client sent
+        assert!(
+            ["ping\n", "going away\n"].contains(&line.as_str()),
+            "{:?}",
+            line
+        );
+    }
+
+    let (status, output) = server.stop();
+    assert!(status.success(), "{}", status);
+    assert!(output.contains(&format!("with {}", backend)), "{}", output);
+    assert!(output.contains("saying goodbye to 2 clients"), "{}", output);
+    let mut rest = String::new();
+    a.read_to_string(&mut rest).unwrap();
+    assert!(rest.ends_with("bye now\n"), "{:?}", rest);
+}
+
+#[test]
+fn poll_backend_relays() {
+    relays(Backend::Poll, 9151);
+}
+
+#[test]
+fn select_backend_relays() {
+    relays(Backend::Select, 9152);
+}
+
+#[test]
+fn epoll_backend_relays() {
+    relays(Backend::Epoll, 9153);
+}
+
+/// A client that doesn't read is disconnected, instead of stopping everybody else
+#[test]
+fn slow_client_is_left_behind() {
+    // more than the send buffer and the outbox of the slow client can hold
+    const TOTAL: usize = 8 << 20;
+
+    for (backend, port) in BACKENDS.into_iter().zip(9154..) {
+        let server = Server::start(&[
+            "chat-server",
+            "--port",
+            &port.to_string(),
+            "--backend",
+            &backend.to_string(),
+        ]);
+        // a fixed receive buffer doesn't grow, it's full after a few messages
+        let slow = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+        slow.set_recv_buffer_size(4096).unwrap();
+        let addr: SocketAddr = ([127, 0, 0, 1], port).into();
+        slow.connect(&addr.into()).unwrap();
+        let mut a = connect(port);
+        let b = connect(port);
+        for _ in 0..3 {
+            server.wait_for("[Server] New connection");
+        }
+        // a blocked relay would stop reading from a too
+        a.set_write_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let reader = thread::spawn(move || {
+            let mut b = b;
+            let mut buf = vec![0u8; 1 << 16];
+            let mut total = 0;
+            while total < TOTAL {
+                let n = b.read(&mut buf).unwrap();
+                assert_ne!(n, 0, "server closed the connection");
+                total += n;
+            }
+            b
+        });
+        let chunk = vec![b'x'; 1 << 16];
+        for _ in 0..TOTAL / chunk.len() {
+            a.write_all(&chunk).unwrap();
+        }
+        let mut b = reader.join().unwrap();
+        let dropped = server.wait_for("[Client] send on fd");
+        assert!(dropped.ends_with("bytes it doesn't read"), "{}", dropped);
+
+        a.write_all(b"still here\n").unwrap();
+        let mut line = [0u8; 11];
+        b.read_exact(&mut line).unwrap();
+        assert_eq!(&line, b"still here\n", "{}", backend);
+
+        let (status, output) = server.stop();
+        assert!(status.success(), "{}", status);
+        assert!(output.contains("saying goodbye to 2 clients"), "{}", output);
+        drop(slow);
+    }
+}
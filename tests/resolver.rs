@@ -0,0 +1,229 @@
+use std::{error::Error, ffi::CString, net::SocketAddr, process::Command, process::ExitCode};
+
+use beej_rs::{
+    builders::AddrInfo,
+    cli,
+    resolver::{self, NameInfo, ResolveError},
+    types::{Family, Flags, NameInfoFlags, Protocol, SocketType},
+};
+use nix::errno::Errno;
+use socket2::SockAddr;
+
+#[test]
+fn numeric_host_gives_one_entry() {
+    let hints = AddrInfo::builder().family(Family::Ipv4).build();
+    let res = resolver::getaddrinfo(Some("127.0.0.1"), Some("80"), hints).unwrap();
+
+    let entries: Vec<_> = res.iter().collect();
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry.family(), Family::Ipv4);
+    assert_eq!(entry.socktype().unwrap(), SocketType::Stream);
+    assert_eq!(entry.protocol().unwrap(), Protocol::Tcp);
+    assert_eq!(
+        entry.socket_args(),
+        (libc::AF_INET, libc::SOCK_STREAM, libc::IPPROTO_TCP)
+    );
+    let addr: SocketAddr = "127.0.0.1:80".parse().unwrap();
+    assert_eq!(entry.socket_addr(), Some(addr));
+    assert_eq!(entry.sockaddr().as_socket(), Some(addr));
+    assert_eq!(entry.canonname(), None);
+}
+
+#[test]
+fn localhost_entries_are_loopback() {
+    let hints = AddrInfo::builder().build();
+    let res = resolver::getaddrinfo(Some("localhost"), Some("3490"), hints).unwrap();
+
+    let mut count = 0;
+    for entry in &res {
+        let addr = entry.socket_addr().unwrap();
+        assert!(addr.ip().is_loopback(), "{}", addr);
+        assert_eq!(addr.port(), 3490);
+        assert_eq!(entry.socktype().unwrap(), SocketType::Stream);
+        assert_eq!(entry.protocol().unwrap(), Protocol::Tcp);
+        let family = match addr {
+            SocketAddr::V4(_) => Family::Ipv4,
+            SocketAddr::V6(_) => Family::Ipv6,
+        };
+        assert_eq!(entry.family(), family);
+        count += 1;
+    }
+    assert!(count > 0);
+
+    // iterating doesn't consume the list
+    assert_eq!(res.iter().count(), count);
+}
+
+/// Every `EAI_*` code glibc returns, the last ones are glibc extensions
+fn eai_codes() -> Vec<(libc::c_int, ResolveError)> {
+    vec![
+        (-9, ResolveError::AddrFamily),
+        (libc::EAI_AGAIN, ResolveError::Again),
+        (libc::EAI_BADFLAGS, ResolveError::BadFlags),
+        (libc::EAI_FAIL, ResolveError::Fail),
+        (libc::EAI_FAMILY, ResolveError::Family),
+        (libc::EAI_MEMORY, ResolveError::Memory),
+        (libc::EAI_NODATA, ResolveError::NoData),
+        (libc::EAI_NONAME, ResolveError::NoName),
+        (libc::EAI_OVERFLOW, ResolveError::Overflow),
+        (libc::EAI_SERVICE, ResolveError::Service),
+        (libc::EAI_SOCKTYPE, ResolveError::SockType),
+        (-100, ResolveError::InProgress),
+        (-101, ResolveError::Canceled),
+        (-102, ResolveError::NotCanceled),
+        (-103, ResolveError::AllDone),
+        (-104, ResolveError::Interrupted),
+        (-105, ResolveError::IdnEncode),
+    ]
+}
+
+#[test]
+fn every_eai_code_has_a_variant() {
+    for (code, error) in eai_codes() {
+        assert_eq!(ResolveError::from_code(code), error, "{}", code);
+        assert_eq!(error.code(), Some(code), "{:?}", error);
+    }
+
+    assert_eq!(ResolveError::from_code(-42), ResolveError::Unknown(-42));
+    assert_eq!(ResolveError::Unknown(-42).code(), Some(-42));
+}
+
+#[test]
+fn eai_system_carries_errno() {
+    Errno::EMFILE.set();
+    let error = ResolveError::from_code(libc::EAI_SYSTEM);
+    assert_eq!(error, ResolveError::System(Errno::EMFILE));
+    assert_eq!(error.code(), Some(libc::EAI_SYSTEM));
+    assert_eq!(error.to_string(), "System error: Too many open files");
+    assert!(error.source().is_some());
+}
+
+#[test]
+fn errors_are_described_by_gai_strerror() {
+    assert_eq!(
+        ResolveError::NoName.to_string(),
+        "Name or service not known"
+    );
+    assert_eq!(
+        ResolveError::Again.to_string(),
+        "Temporary failure in name resolution"
+    );
+    let nul = CString::new("local\0host").unwrap_err();
+    let error = ResolveError::from(nul);
+    assert_eq!(error.code(), None);
+    assert!(error.to_string().starts_with("Invalid name: "));
+}
+
+#[test]
+fn exit_codes_follow_sysexits() {
+    let table = [
+        (ResolveError::NoName, 68),
+        (ResolveError::NoData, 68),
+        (ResolveError::AddrFamily, 68),
+        (ResolveError::Again, 75),
+        (ResolveError::Interrupted, 75),
+        (ResolveError::Fail, 69),
+        (ResolveError::BadFlags, 64),
+        (ResolveError::Family, 64),
+        (ResolveError::SockType, 64),
+        (ResolveError::Service, 64),
+        (ResolveError::IdnEncode, 64),
+        (
+            ResolveError::InvalidName(CString::new("a\0b").unwrap_err()),
+            64,
+        ),
+        (ResolveError::Memory, 71),
+        (ResolveError::Overflow, 71),
+        (ResolveError::System(Errno::EIO), 71),
+        (ResolveError::InProgress, 70),
+        (ResolveError::Canceled, 70),
+        (ResolveError::NotCanceled, 70),
+        (ResolveError::AllDone, 70),
+        (ResolveError::Unknown(-42), 70),
+    ];
+    for (error, code) in table {
+        assert_eq!(cli::exit_code(&error), ExitCode::from(code), "{:?}", error);
+    }
+}
+
+#[test]
+fn unknown_host_exits_with_ex_nohost() {
+    // numerichost fails without asking any name server
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["show-ip", "localhost", "--flag", "numerichost"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(68));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "show-ip: Name or service not known\n"
+    );
+}
+
+#[test]
+fn canonname_is_on_the_first_entry() {
+    let hints = AddrInfo::builder()
+        .socktype(None)
+        .flags(Flags::CANONNAME)
+        .build();
+    let res = resolver::getaddrinfo(Some("localhost"), None, hints).unwrap();
+
+    let canonnames: Vec<_> = res
+        .iter()
+        .map(|entry| entry.canonname().map(String::from))
+        .collect();
+    assert_eq!(canonnames[0].as_deref(), Some("localhost"));
+    assert!(canonnames[1..].iter().all(Option::is_none));
+}
+
+fn sockaddr(addr: &str) -> SockAddr {
+    SockAddr::from(addr.parse::<SocketAddr>().unwrap())
+}
+
+#[test]
+fn getnameinfo_numeric_host() {
+    let nameinfo =
+        resolver::getnameinfo(&sockaddr("127.0.0.1:80"), NameInfoFlags::NUMERICHOST).unwrap();
+    assert_eq!(nameinfo.host, "127.0.0.1");
+    // from /etc/services
+    assert_eq!(nameinfo.service, "http");
+
+    let nameinfo =
+        resolver::getnameinfo(&sockaddr("[::1]:22"), NameInfoFlags::NUMERICHOST).unwrap();
+    assert_eq!(nameinfo.host, "::1");
+    assert_eq!(nameinfo.service, "ssh");
+}
+
+#[test]
+fn getnameinfo_numeric_service() {
+    let flags = NameInfoFlags::NUMERICHOST | NameInfoFlags::NUMERICSERV;
+    let nameinfo = resolver::getnameinfo(&sockaddr("127.0.0.1:80"), flags).unwrap();
+    assert_eq!(
+        nameinfo,
+        NameInfo {
+            host: "127.0.0.1".to_string(),
+            service: "80".to_string(),
+        }
+    );
+}
+
+#[test]
+fn reverse_prints_host_and_service() {
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args([
+            "reverse",
+            "127.0.0.1",
+            "--port",
+            "80",
+            "--flag",
+            "numerichost",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Names for '127.0.0.1'\n\n\tHost: 127.0.0.1\n\tService: http\n"
+    );
+}
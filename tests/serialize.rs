@@ -0,0 +1,210 @@
+use std::process::Command;
+
+use beej_rs::{
+    byteorder,
+    serialize::{pack, parse_values, unpack, PackError, Packer, Unpacker, Value},
+};
+use proptest::prelude::*;
+
+mod common;
+use common::Server;
+
+fn value() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        any::<i16>().prop_map(Value::I16),
+        any::<u16>().prop_map(Value::U16),
+        any::<i32>().prop_map(Value::I32),
+        any::<u32>().prop_map(Value::U32),
+        any::<i64>().prop_map(Value::I64),
+        any::<u64>().prop_map(Value::U64),
+        any::<u32>().prop_map(|bits| Value::F32(f32::from_bits(bits))),
+        any::<u64>().prop_map(|bits| Value::F64(f64::from_bits(bits))),
+        ".{0,40}".prop_map(Value::Str),
+    ]
+}
+
+/// Equality that sees NaNs with the same bits as equal
+fn same(a: &[Value], b: &[Value]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|pair| match pair {
+            (Value::F32(a), Value::F32(b)) => a.to_bits() == b.to_bits(),
+            (Value::F64(a), Value::F64(b)) => a.to_bits() == b.to_bits(),
+            (a, b) => a == b,
+        })
+}
+
+proptest! {
+    #[test]
+    fn pack_unpack_round_trip(values in prop::collection::vec(value(), 0..20)) {
+        let format: String = values.iter().map(Value::code).collect();
+        let buf = pack(&format, &values).unwrap();
+        let unpacked = unpack(&format, &buf).unwrap();
+        prop_assert!(same(&values, &unpacked), "{:?} != {:?}", values, unpacked);
+    }
+
+    #[test]
+    fn packer_matches_pack(values in prop::collection::vec(value(), 0..20)) {
+        let format: String = values.iter().map(Value::code).collect();
+        let mut packer = Packer::new();
+        for value in &values {
+            packer.value(value.clone());
+        }
+        prop_assert_eq!(packer.finish().unwrap(), pack(&format, &values).unwrap());
+    }
+
+    #[test]
+    fn floats_are_packed_like_pack754(bits in any::<u32>(), wide_bits in any::<u64>()) {
+        let (f, d) = (f32::from_bits(bits), f64::from_bits(wide_bits));
+        prop_assume!(!f.is_nan() && !d.is_nan());
+        let buf = pack("fd", &[Value::F32(f), Value::F64(d)]).unwrap();
+        prop_assert_eq!(&buf[..4], byteorder::pack754_32(f).to_be_bytes());
+        prop_assert_eq!(&buf[4..], byteorder::pack754_64(d).to_be_bytes());
+    }
+
+    #[test]
+    fn truncated_buffers_fail(values in prop::collection::vec(value(), 1..10), cut in 1usize..8) {
+        let format: String = values.iter().map(Value::code).collect();
+        let buf = pack(&format, &values).unwrap();
+        let cut = cut.min(buf.len());
+        let truncated = unpack(&format, &buf[..buf.len() - cut]);
+        prop_assert!(
+            matches!(truncated, Err(PackError::Truncated { .. })),
+            "{:?}",
+            truncated
+        );
+    }
+
+    #[test]
+    fn garbage_never_panics(format in "[hHlLqQfds0-9 ]{0,10}", buf in prop::collection::vec(any::<u8>(), 0..64)) {
+        let _ = unpack(&format, &buf);
+    }
+}
+
+#[test]
+fn network_byte_order() {
+    let buf = pack(
+        "hHlqs",
+        &[
+            Value::I16(-2),
+            Value::U16(0x0102),
+            Value::I32(0x01020304),
+            Value::I64(1),
+            Value::Str("ok".into()),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        buf,
+        b"\xff\xfe\x01\x02\x01\x02\x03\x04\x00\x00\x00\x00\x00\x00\x00\x01\x00\x02ok"
+    );
+
+    let buf = Packer::new().f32(1.0).f64(-2.0).finish().unwrap();
+    assert_eq!(buf, b"\x3f\x80\x00\x00\xc0\x00\x00\x00\x00\x00\x00\x00");
+}
+
+#[test]
+fn typed_unpacker() {
+    let buf = Packer::new()
+        .u32(7)
+        .str("Beej")
+        .f64(3.490)
+        .finish()
+        .unwrap();
+    let mut unpacker = Unpacker::new(&buf);
+    assert_eq!(unpacker.u32().unwrap(), 7);
+    assert_eq!(unpacker.str().unwrap(), "Beej");
+    assert_eq!(unpacker.f64().unwrap(), 3.490);
+    unpacker.finish().unwrap();
+}
+
+#[test]
+fn format_errors() {
+    assert_eq!(pack("x", &[]), Err(PackError::UnknownCode('x')));
+    assert_eq!(pack("3h", &[Value::I16(1)]), Err(PackError::BadCount));
+    assert_eq!(
+        pack("h", &[Value::U16(1)]),
+        Err(PackError::Mismatch {
+            index: 0,
+            expected: 'h'
+        })
+    );
+    assert_eq!(
+        pack("hh", &[Value::I16(1)]),
+        Err(PackError::MissingValue { index: 1 })
+    );
+    assert_eq!(
+        pack("", &[Value::I16(1)]),
+        Err(PackError::ExtraValues { count: 1 })
+    );
+    assert_eq!(
+        pack("3s", &[Value::Str("four".into())]),
+        Err(PackError::StrTooLong { len: 4, max: 3 })
+    );
+    assert_eq!(
+        Packer::new().str(&"x".repeat(70_000)).finish(),
+        Err(PackError::StrTooLong {
+            len: 70_000,
+            max: 65535
+        })
+    );
+    assert_eq!(
+        unpack("h", b"\x00\x01\x02"),
+        Err(PackError::TrailingBytes(1))
+    );
+    assert_eq!(unpack("s", b"\x00\x01\xff"), Err(PackError::InvalidUtf8));
+    assert_eq!(
+        unpack("3s", b"\x00\x04four"),
+        Err(PackError::StrTooLong { len: 4, max: 3 })
+    );
+}
+
+#[test]
+fn values_from_text() {
+    assert_eq!(
+        parse_values("hQ s d", &["-3", "18446744073709551615", "hi there", "0.5"]).unwrap(),
+        [
+            Value::I16(-3),
+            Value::U64(u64::MAX),
+            Value::Str("hi there".into()),
+            Value::F64(0.5)
+        ]
+    );
+    assert_eq!(
+        parse_values("H", &["-1"]),
+        Err(PackError::InvalidValue {
+            code: 'H',
+            value: "-1".into()
+        })
+    );
+}
+
+#[test]
+fn talker_to_listener() {
+    let listener = Server::start(&["socket-listener", "--port", "9141", "--unpack", "hsd"]);
+
+    let talker = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["socket-talker", "--port", "9141", "--pack", "hsd"])
+        .args(["--", "-42", "hello world", "2.5"])
+        .status()
+        .unwrap();
+    assert!(talker.success());
+
+    let line = listener.next_line();
+    let (status, _) = listener.stop();
+    assert!(status.success(), "{}", status);
+    assert_eq!(line, "-42 \"hello world\" 2.5");
+}
+
+#[test]
+fn listener_survives_bytes_that_arent_text() {
+    let listener = Server::start(&["socket-listener", "--port", "9163", "--family", "ipv4"]);
+
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.send_to(b"caf\xe9", "127.0.0.1:9163").unwrap();
+    socket.send_to(b"still here", "127.0.0.1:9163").unwrap();
+
+    assert_eq!(listener.next_line(), "caf\u{fffd}");
+    assert_eq!(listener.next_line(), "still here");
+    let (status, _) = listener.stop();
+    assert!(status.success(), "{}", status);
+}
@@ -0,0 +1,85 @@
+use std::process::Command;
+
+fn show_ip(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .arg("show-ip")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// The schema CI diffs against: names, order and types of the fields
+#[test]
+fn json_schema_is_stable() {
+    let json = show_ip(&[
+        "127.0.0.1",
+        "--service",
+        "80",
+        "--socktype",
+        "stream",
+        "--format",
+        "json",
+    ]);
+    assert_eq!(
+        json,
+        r#"[
+  {
+    "address": "127.0.0.1",
+    "port": 80,
+    "family": "ipv4",
+    "socktype": "stream",
+    "protocol": "tcp",
+    "canonname": null
+  }
+]
+"#
+    );
+}
+
+#[test]
+fn csv_schema_is_stable() {
+    let csv = show_ip(&[
+        "127.0.0.1",
+        "--service",
+        "80",
+        "--socktype",
+        "stream",
+        "--format",
+        "csv",
+    ]);
+    assert_eq!(
+        csv,
+        "address,port,family,socktype,protocol,canonname\n127.0.0.1,80,ipv4,stream,tcp,\n"
+    );
+}
+
+#[test]
+fn every_field_is_named() {
+    let json = show_ip(&[
+        "127.0.0.1",
+        "--service",
+        "53",
+        "--socktype",
+        "datagram",
+        "--format",
+        "json",
+    ]);
+    let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let record = &records[0];
+    assert_eq!(record["socktype"], "datagram");
+    assert_eq!(record["protocol"], "udp");
+}
+
+// Without --socktype there is no hint, and glibc answers for every type it knows
+#[test]
+fn no_socktype_hint_lists_every_type() {
+    let csv = show_ip(&["127.0.0.1", "--service", "80", "--format", "csv"]);
+    let socktypes: Vec<_> = csv
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(3).unwrap())
+        .collect();
+    assert_eq!(socktypes, ["stream", "datagram", "raw"]);
+}
@@ -0,0 +1,138 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    process::Command,
+    time::Duration,
+};
+
+use beej_rs::types::Backend;
+use nix::sys::signal::Signal;
+
+mod common;
+use common::Server;
+
+fn connect(port: u16) -> TcpStream {
+    let stream = TcpStream::connect(("localhost", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+}
+
+/// Both clients are connected once a message from one reaches the other
+fn connect_pair(port: u16) -> (TcpStream, TcpStream) {
+    let mut a = connect(port);
+    let mut b = connect(port);
+    let mut buf = [0u8; 5];
+    for _ in 0..50 {
+        a.write_all(b"ping\n").unwrap();
+        b.set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        if b.read_exact(&mut buf).is_ok() {
+            b.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            return (a, b);
+        }
+    }
+    panic!("clients never saw each other");
+}
+
+fn read_all(mut stream: TcpStream) -> String {
+    let mut received = String::new();
+    stream.read_to_string(&mut received).unwrap();
+    received
+}
+
+fn says_goodbye(backend: Backend, port: u16) {
+    let server = Server::start(&[
+        "chat-server",
+        "--port",
+        &port.to_string(),
+        "--backend",
+        &backend.to_string(),
+        "--goodbye",
+        "bye now\n",
+    ]);
+    let (a, b) = connect_pair(port);
+
+    let (status, output) = server.stop_with(Signal::SIGTERM);
+    assert!(status.success(), "{}", status);
+    assert!(output.contains("saying goodbye to 2 clients"), "{}", output);
+
+    // whatever was still on the way, then the goodbye, then EOF
+    assert!(read_all(a).ends_with("bye now\n"));
+    assert!(read_all(b).ends_with("bye now\n"));
+}
+
+#[test]
+fn poll_backend_says_goodbye() {
+    says_goodbye(Backend::Poll, 9134);
+}
+
+#[test]
+fn select_backend_says_goodbye() {
+    says_goodbye(Backend::Select, 9135);
+}
+
+#[test]
+fn old_names_stop_without_clients() {
+    // poll-server is an alias, select-server picks the select backend
+    for (command, port, backend) in [
+        ("poll-server", 9136, "poll"),
+        ("select-server", 9161, "select"),
+    ] {
+        let server = Server::start(&[command, "--port", &port.to_string()]);
+        let (status, output) = server.stop_with(Signal::SIGINT);
+        assert!(status.success(), "{}", status);
+        let listening = format!("Listening on [::]:{} with {}\n", port, backend);
+        assert!(output.starts_with(&listening), "{}", output);
+    }
+
+    // unless told otherwise
+    let server = Server::start(&["select-server", "--port", "9162", "--backend", "epoll"]);
+    let (_, output) = server.stop();
+    assert!(
+        output.starts_with("Listening on [::]:9162 with epoll\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn socketlistener_stops() {
+    let server = Server::start(&["socket-listener", "--port", "9137"]);
+    let (status, _) = server.stop_with(Signal::SIGTERM);
+    assert!(status.success(), "{}", status);
+}
+
+#[test]
+fn goodbye_that_cant_be_sent_is_a_usage_error() {
+    let chat_server = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+            .args(["chat-server", "--port", "9158"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = chat_server(&[
+        "--framing",
+        "u16",
+        "--max-frame-size",
+        "4",
+        "--goodbye",
+        "too long\n",
+    ]);
+    assert_eq!(output.status.code(), Some(64));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "chat-server: --goodbye: frame of 8 bytes is larger than 4 bytes\n"
+    );
+
+    // only the last newline is the end of the line
+    let output = chat_server(&["--framing", "line", "--goodbye", "two\nlines\n"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "chat-server: --goodbye: newline inside a line frame\n"
+    );
+}
@@ -0,0 +1,80 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    os::fd::{AsRawFd, OwnedFd},
+    thread,
+};
+
+use beej_rs::{
+    socket::Socket,
+    types::{AddressFamily, SocketType},
+};
+use socket2::SockAddr;
+
+fn listener() -> (Socket, SockAddr) {
+    let socket = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    socket.set_reuseaddr(true).unwrap();
+    socket
+        .bind(&SocketAddr::from((Ipv4Addr::LOCALHOST, 0)).into())
+        .unwrap();
+    socket.listen(1).unwrap();
+    let addr = socket.local_addr().unwrap();
+    (socket, addr)
+}
+
+#[test]
+fn stream_round_trip() {
+    let (listener, addr) = listener();
+
+    let server = thread::spawn(move || {
+        let (conn, peer) = listener.accept().unwrap();
+        assert!(peer.as_socket_ipv4().is_some());
+        conn.send(b"Hello, world!").unwrap()
+    });
+
+    let client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    client.connect(&addr).unwrap();
+    let mut buf = [0u8; 100];
+    let len = client.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"Hello, world!");
+    assert_eq!(server.join().unwrap(), len);
+
+    // the server side is closed once its socket is dropped
+    assert_eq!(client.recv(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn connect_error_is_errno() {
+    let (listener, addr) = listener();
+    drop(listener);
+
+    let client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    let err = client.connect(&addr).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::ECONNREFUSED));
+}
+
+#[test]
+fn converts_without_closing() {
+    let socket = Socket::new(AddressFamily::Inet6, SocketType::Datagram, None).unwrap();
+    let fd = socket.as_raw_fd();
+
+    let socket: socket2::Socket = socket.into();
+    assert_eq!(socket.as_raw_fd(), fd);
+    assert_eq!(socket.r#type().unwrap(), socket2::Type::DGRAM);
+
+    let socket: Socket = socket.into();
+    let owned: OwnedFd = socket.into();
+    assert_eq!(owned.as_raw_fd(), fd);
+
+    // nix sockets are plain OwnedFds
+    let socket: Socket = nix::sys::socket::socket(
+        nix::sys::socket::AddressFamily::Inet,
+        nix::sys::socket::SockType::Stream,
+        nix::sys::socket::SockFlag::empty(),
+        None,
+    )
+    .unwrap()
+    .into();
+    socket
+        .bind(&SocketAddr::from((Ipv4Addr::LOCALHOST, 0)).into())
+        .unwrap();
+}
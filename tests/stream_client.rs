@@ -0,0 +1,105 @@
+use std::{
+    fs,
+    io::Write,
+    net::{Ipv4Addr, TcpListener},
+    process::{Command, Output},
+    thread,
+    time::Duration,
+};
+
+/// Serve `reply` once on a free port, then close
+fn one_shot_server(reply: Vec<u8>, linger: Duration) -> u16 {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = stream.write_all(&reply);
+        thread::sleep(linger);
+    });
+    port
+}
+
+fn client(port: u16, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["stream-client", "127.0.0.1", "--port", &port.to_string()])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn payload(len: usize) -> Vec<u8> {
+    // no NUL on purpose, and more than fits in one receive
+    (0..len).map(|i| b'a' + (i % 26) as u8).collect()
+}
+
+#[test]
+fn reads_until_eof() {
+    let reply = payload(100_000);
+    let port = one_shot_server(reply.clone(), Duration::ZERO);
+
+    let output = client(port, &[]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, reply);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("client: received 100000 bytes"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn writes_to_output_file() {
+    let reply = payload(1000);
+    let port = one_shot_server(reply.clone(), Duration::ZERO);
+    let path = std::env::temp_dir().join(format!("beej-client-{}", std::process::id()));
+
+    let output = client(port, &["--output", path.to_str().unwrap()]);
+    let written = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(written, reply);
+}
+
+#[test]
+fn stops_at_max_bytes() {
+    // the server keeps the connection open, we must not wait for EOF
+    let port = one_shot_server(payload(1000), Duration::from_secs(5));
+
+    let output = client(port, &["--max-bytes", "250", "--timeout", "2000"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, payload(250));
+}
+
+#[test]
+fn times_out_on_silent_server() {
+    let port = one_shot_server(b"partial".to_vec(), Duration::from_secs(5));
+
+    let output = client(port, &["--timeout", "200"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"partial");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("timed out after 7 bytes"), "{}", stderr);
+}
+
+#[test]
+fn connect_timeout_reports_the_address() {
+    // bind then drop, so nobody listens on the port
+    let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let output = client(port, &["--connect-timeout", "200"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!("client: connect 127.0.0.1:{} err:", port)),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("(os error 111)"), "{}", stderr);
+}
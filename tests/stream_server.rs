@@ -0,0 +1,145 @@
+use std::{
+    fs,
+    io::Read,
+    net::TcpStream,
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+use common::Server;
+
+/// `args` should raise the backlog: with the default of 10,
+/// the kernel drops some of the handshakes of many concurrent clients
+fn start_server(port: u16, args: &[&str]) -> Server {
+    Server::start_when(
+        &[&["stream-server", "--port", &port.to_string()][..], args].concat(),
+        "server: waiting for connections",
+    )
+}
+
+fn hello(port: u16) -> String {
+    let mut stream = TcpStream::connect(("localhost", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    reply
+}
+
+fn concurrent_hellos(port: u16, clients: usize) {
+    let clients: Vec<_> = (0..clients)
+        .map(|_| thread::spawn(move || hello(port)))
+        .collect();
+    for client in clients {
+        assert_eq!(client.join().unwrap(), "Hello, world!");
+    }
+}
+
+/// Children of `pid` that are dead but not reaped yet
+fn zombies(pid: u32) -> Vec<String> {
+    let mut zombies = Vec::new();
+    for entry in fs::read_dir("/proc").unwrap().flatten() {
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        // the command name is in parentheses and can contain spaces
+        let Some((_, fields)) = stat.rsplit_once(") ") else {
+            continue;
+        };
+        let fields: Vec<&str> = fields.split(' ').collect();
+        if fields[0] == "Z" && fields[1] == pid.to_string() {
+            zombies.push(stat);
+        }
+    }
+    zombies
+}
+
+#[test]
+fn fork_mode_serves_concurrent_clients_without_zombies() {
+    let server = start_server(3491, &["--mode", "fork", "--backlog", "128"]);
+    concurrent_hellos(3491, 100);
+
+    // the last children might still be exiting
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let zombies = zombies(server.pid());
+        if zombies.is_empty() {
+            break;
+        }
+        assert!(Instant::now() < deadline, "zombies left: {:?}", zombies);
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn thread_mode_counts_served_connections() {
+    let server = start_server(3492, &["--mode", "thread", "--backlog", "128"]);
+    concurrent_hellos(3492, 100);
+
+    let (status, output) = server.stop();
+    assert!(status.success(), "server failed: {}", status);
+    assert!(
+        output.contains("server: served 100 connections"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn pool_mode_counts_served_connections() {
+    let server = start_server(
+        3493,
+        &["--mode", "pool", "--workers", "3", "--backlog", "128"],
+    );
+    concurrent_hellos(3493, 100);
+
+    let (status, output) = server.stop();
+    assert!(status.success(), "server failed: {}", status);
+    assert!(
+        output.contains("server: served 100 connections"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn banner_file_and_client_settings() {
+    let banner = std::env::temp_dir().join(format!("beej-banner-{}", std::process::id()));
+    fs::write(&banner, "Welcome to the lab\n").unwrap();
+
+    let server = start_server(
+        3494,
+        &[
+            "--family",
+            "ipv4",
+            "--banner-file",
+            banner.to_str().unwrap(),
+        ],
+    );
+    let client = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["stream-client", "localhost", "--port", "3494"])
+        .args(["--family", "ipv4", "--max-data-size", "4"])
+        .output()
+        .unwrap();
+    fs::remove_file(&banner).unwrap();
+
+    assert!(client.status.success());
+    let stderr = String::from_utf8(client.stderr).unwrap();
+    assert!(stderr.contains("IP: 127.0.0.1"), "{}", stderr);
+    // small receives, but we still get everything
+    assert_eq!(client.stdout, b"Welcome to the lab\n");
+    let (status, _) = server.stop();
+    assert!(status.success(), "server failed: {}", status);
+}
+
+#[test]
+fn missing_banner_file() {
+    let output = Command::new(env!("CARGO_BIN_EXE_beej-rs"))
+        .args(["stream-server", "--banner-file", "/nonexistent/banner"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(66));
+}
@@ -0,0 +1,114 @@
+use beej_rs::{
+    builders::AddrInfo,
+    resolver,
+    types::{AddressFamily, Flag, Flags, Protocol, SocketType},
+};
+
+#[test]
+fn socket_types_round_trip() {
+    for socktype in [
+        SocketType::Stream,
+        SocketType::Datagram,
+        SocketType::SeqPacket,
+        SocketType::Raw,
+        SocketType::Rdm,
+    ] {
+        let raw: libc::c_int = socktype.into();
+        assert_eq!(SocketType::try_from(raw).unwrap(), socktype);
+
+        let nix: nix::sys::socket::SockType = socktype.into();
+        assert_eq!(SocketType::try_from(nix).unwrap(), socktype);
+    }
+}
+
+#[test]
+fn unknown_socket_type_is_an_error() {
+    let err = SocketType::try_from(42).unwrap_err();
+    assert_eq!(err.value, 42);
+    assert_eq!(err.to_string(), "Unknown socket type: 42");
+}
+
+#[test]
+fn address_families_round_trip() {
+    for family in [
+        AddressFamily::Unspecified,
+        AddressFamily::Inet,
+        AddressFamily::Inet6,
+        AddressFamily::Unix,
+        AddressFamily::Packet,
+    ] {
+        let raw: libc::c_int = family.into();
+        assert_eq!(AddressFamily::try_from(raw).unwrap(), family);
+
+        let nix: nix::sys::socket::AddressFamily = family.into();
+        assert_eq!(AddressFamily::try_from(nix).unwrap(), family);
+    }
+    assert!(AddressFamily::try_from(libc::AF_BLUETOOTH).is_err());
+}
+
+#[test]
+fn protocols_round_trip() {
+    for protocol in [
+        Protocol::Tcp,
+        Protocol::Udp,
+        Protocol::Sctp,
+        Protocol::Icmp,
+        Protocol::IcmpV6,
+    ] {
+        let raw: libc::c_int = protocol.into();
+        assert_eq!(Protocol::try_from(raw).unwrap(), protocol);
+    }
+
+    let nix = nix::sys::socket::SockProtocol::try_from(Protocol::Tcp).unwrap();
+    assert_eq!(Protocol::try_from(nix).unwrap(), Protocol::Tcp);
+    assert!(nix::sys::socket::SockProtocol::try_from(Protocol::Sctp).is_err());
+}
+
+// Without a socktype hint nor a service, glibc also returns SOCK_RAW entries
+#[test]
+fn getaddrinfo_without_socktype_hint() {
+    let hints = AddrInfo::builder().socktype(None).build();
+    let res = resolver::getaddrinfo(Some("127.0.0.1"), None, hints).unwrap();
+    let socktypes: Vec<_> = res.iter().map(|entry| entry.socktype().unwrap()).collect();
+    assert!(socktypes.contains(&SocketType::Stream));
+    assert!(socktypes.contains(&SocketType::Datagram));
+    assert!(socktypes.contains(&SocketType::Raw));
+}
+
+#[test]
+fn flags_map_to_ai_bits() {
+    for (flag, bits) in [
+        (Flag::Passive, libc::AI_PASSIVE),
+        (Flag::CanonName, libc::AI_CANONNAME),
+        (Flag::NumericHost, libc::AI_NUMERICHOST),
+        (Flag::V4Mapped, libc::AI_V4MAPPED),
+        (Flag::All, libc::AI_ALL),
+        (Flag::AddrConfig, libc::AI_ADDRCONFIG),
+        (Flag::NumericServ, libc::AI_NUMERICSERV),
+        // glibc's AI_IDN and AI_CANONIDN
+        (Flag::Idn, 0x0040),
+        (Flag::CanonIdn, 0x0080),
+    ] {
+        assert_eq!(libc::c_int::from(Flags::from(flag)), bits, "{:?}", flag);
+    }
+}
+
+#[test]
+fn flags_collect_from_the_cli() {
+    let flags: Flags = vec![Flag::Passive, Flag::NumericServ, Flag::Passive]
+        .into_iter()
+        .collect();
+    assert_eq!(flags, Flags::PASSIVE | Flags::NUMERICSERV);
+    assert_eq!(
+        libc::c_int::from(flags),
+        libc::AI_PASSIVE | libc::AI_NUMERICSERV
+    );
+
+    let none: Flags = Vec::<Flag>::new().into_iter().collect();
+    assert_eq!(none, Flags::empty());
+
+    // and they reach the hints
+    let hints = AddrInfo::builder().flags(flags).build();
+    let addrinfo: libc::addrinfo = hints.into();
+    assert_eq!(addrinfo.ai_flags, libc::AI_PASSIVE | libc::AI_NUMERICSERV);
+}